@@ -0,0 +1,523 @@
+// Generates instruction builders and decoders from the checked-in Anchor IDLs, laid out like an
+// Anchor client: `accounts::X` for the named accounts of instruction `x`, `instruction::X` for its
+// args and discriminator, `types` for the IDL defined types, and `decode` for the reverse.
+// Upstream IDLs are read as published, legacy or Anchor 0.30: nested account groups are flattened
+// into `<group>_<account>` fields and enum variants may carry fields.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::path::Path;
+use std::{env, fs};
+
+// IDL and the file generated from it in OUT_DIR
+const IDLS: [(&str, &str); 2] = [
+    ("idl/kamino_lending.json", "klend_instructions.rs"),
+    ("idl/kfarm.json", "kfarm_instructions.rs"),
+];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    for (idl_path, generated) in IDLS {
+        println!("cargo:rerun-if-changed={}", idl_path);
+
+        let idl: Value = serde_json::from_str(&fs::read_to_string(idl_path).unwrap())
+            .unwrap_or_else(|e| panic!("{} is not valid JSON: {}", idl_path, e));
+
+        fs::write(
+            Path::new(&out_dir).join(generated),
+            generate(idl_path, &idl),
+        )
+        .unwrap();
+    }
+}
+
+fn generate(idl_path: &str, idl: &Value) -> String {
+    let mut code = String::new();
+    writeln!(
+        code,
+        "// Generated by build.rs from {}, do not edit.",
+        idl_path
+    )
+    .unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "use borsh::{{BorshDeserialize, BorshSerialize}};").unwrap();
+    writeln!(
+        code,
+        "use solana_program::instruction::{{AccountMeta, Instruction}};"
+    )
+    .unwrap();
+    writeln!(code, "use solana_program::pubkey::Pubkey;").unwrap();
+    writeln!(code).unwrap();
+    writeln!(
+        code,
+        "pub const ID: Pubkey = solana_program::pubkey!(\"{}\");",
+        program_address(idl)
+    )
+    .unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "#[derive(Clone, Debug, PartialEq)]").unwrap();
+    writeln!(code, "pub struct IdlInstruction {{").unwrap();
+    writeln!(code, "    pub name: &'static str,").unwrap();
+    writeln!(code, "    pub account_names: &'static [&'static str],").unwrap();
+    writeln!(code, "    /// Debug formatted args").unwrap();
+    writeln!(code, "    pub args: String,").unwrap();
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+    // Anchor passes the program id for an optional account that is left out
+    writeln!(
+        code,
+        "fn optional_account_meta(pubkey: Option<Pubkey>, is_writable: bool) -> AccountMeta {{"
+    )
+    .unwrap();
+    writeln!(code, "    match pubkey {{").unwrap();
+    writeln!(
+        code,
+        "        Some(pubkey) if is_writable => AccountMeta::new(pubkey, false),"
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "        Some(pubkey) => AccountMeta::new_readonly(pubkey, false),"
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "        None => AccountMeta::new_readonly(ID, false),"
+    )
+    .unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+
+    if !array(idl, "types").is_empty() {
+        generate_types(&mut code, idl);
+    }
+    generate_args(&mut code, idl);
+    generate_accounts(&mut code, idl);
+    generate_decode(&mut code, idl);
+
+    code
+}
+
+fn generate_types(code: &mut String, idl: &Value) {
+    writeln!(code).unwrap();
+    writeln!(code, "pub mod types {{").unwrap();
+    // Unused when the IDL only has type aliases
+    writeln!(code, "    #[allow(unused_imports)]").unwrap();
+    writeln!(code, "    use super::*;").unwrap();
+
+    for ty in array(idl, "types") {
+        let name = str_field(ty, "name");
+        let kind = str_field(&ty["type"], "kind");
+        writeln!(code).unwrap();
+        match kind {
+            "struct" => {
+                writeln!(
+                    code,
+                    "    #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]"
+                )
+                .unwrap();
+                let fields = ty["type"].get("fields").map_or(&[][..], |f| {
+                    f.as_array()
+                        .unwrap_or_else(|| panic!("fields of {} are not an array", name))
+                });
+                writeln!(
+                    code,
+                    "    pub struct {}{}",
+                    name,
+                    struct_fields(fields, "    ", true)
+                )
+                .unwrap();
+            }
+            "enum" => {
+                let variants = array(&ty["type"], "variants");
+                if variants.iter().any(|v| v.get("fields").is_some()) {
+                    writeln!(
+                        code,
+                        "    #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]"
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(
+                        code,
+                        "    #[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]"
+                    )
+                    .unwrap();
+                }
+                writeln!(code, "    pub enum {} {{", name).unwrap();
+                for variant in variants {
+                    let fields = match variant.get("fields") {
+                        Some(fields) => struct_fields(
+                            fields.as_array().unwrap_or_else(|| {
+                                panic!("fields of {} variant are not an array", name)
+                            }),
+                            "        ",
+                            false,
+                        ),
+                        None => String::new(),
+                    };
+                    writeln!(code, "        {}{},", str_field(variant, "name"), fields).unwrap();
+                }
+                writeln!(code, "    }}").unwrap();
+            }
+            // Anchor 0.30 type alias
+            "type" => {
+                writeln!(
+                    code,
+                    "    pub type {} = {};",
+                    name,
+                    rust_type(&ty["type"]["alias"])
+                )
+                .unwrap();
+            }
+            _ => panic!("type {} has unsupported kind {}", name, kind),
+        }
+    }
+
+    writeln!(code, "}}").unwrap();
+}
+
+fn generate_args(code: &mut String, idl: &Value) {
+    writeln!(code).unwrap();
+    writeln!(code, "pub mod instruction {{").unwrap();
+    if !array(idl, "types").is_empty() {
+        writeln!(code, "    use super::types::*;").unwrap();
+    }
+    writeln!(code, "    use super::*;").unwrap();
+
+    for ix in array(idl, "instructions") {
+        let name = snake_case(str_field(ix, "name"));
+        let struct_name = pascal_case(str_field(ix, "name"));
+        writeln!(code).unwrap();
+        writeln!(code, "    /// Arguments of `{}`", name).unwrap();
+        writeln!(
+            code,
+            "    #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]"
+        )
+        .unwrap();
+        let args = array(ix, "args");
+        if args.is_empty() {
+            writeln!(code, "    pub struct {} {{}}", struct_name).unwrap();
+        } else {
+            writeln!(code, "    pub struct {} {{", struct_name).unwrap();
+            for arg in args {
+                writeln!(
+                    code,
+                    "        pub {}: {},",
+                    snake_case(str_field(arg, "name")),
+                    rust_type(&arg["type"])
+                )
+                .unwrap();
+            }
+            writeln!(code, "    }}").unwrap();
+        }
+        writeln!(code).unwrap();
+        writeln!(code, "    impl {} {{", struct_name).unwrap();
+        writeln!(code, "        pub const NAME: &str = \"{}\";", name).unwrap();
+        writeln!(
+            code,
+            "        pub const DISCRIMINATOR: [u8; 8] = {:?};",
+            discriminator(ix, &name)
+        )
+        .unwrap();
+        writeln!(code).unwrap();
+        writeln!(code, "        pub fn data(&self) -> Vec<u8> {{").unwrap();
+        writeln!(
+            code,
+            "            let mut data = Self::DISCRIMINATOR.to_vec();"
+        )
+        .unwrap();
+        writeln!(code, "            data.extend(self.try_to_vec().unwrap());").unwrap();
+        writeln!(code, "            data").unwrap();
+        writeln!(code, "        }}").unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    writeln!(code, "}}").unwrap();
+}
+
+fn generate_accounts(code: &mut String, idl: &Value) {
+    writeln!(code).unwrap();
+    writeln!(code, "pub mod accounts {{").unwrap();
+    writeln!(code, "    use super::*;").unwrap();
+
+    for ix in array(idl, "instructions") {
+        let name = snake_case(str_field(ix, "name"));
+        let struct_name = pascal_case(str_field(ix, "name"));
+        let mut accounts = vec![];
+        flatten_accounts(array(ix, "accounts"), "", &mut accounts);
+
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "    /// Accounts of `{}`, optional ones are None when left out",
+            name
+        )
+        .unwrap();
+        writeln!(code, "    #[derive(Clone, Debug, PartialEq)]").unwrap();
+        writeln!(code, "    pub struct {} {{", struct_name).unwrap();
+        for (field, account) in &accounts {
+            let ty = if is_optional(account) {
+                "Option<Pubkey>"
+            } else {
+                "Pubkey"
+            };
+            writeln!(code, "        pub {}: {},", field, ty).unwrap();
+        }
+        writeln!(code, "    }}").unwrap();
+        writeln!(code).unwrap();
+        writeln!(code, "    impl {} {{", struct_name).unwrap();
+        writeln!(
+            code,
+            "        pub const NAMES: [&str; {}] = [",
+            accounts.len()
+        )
+        .unwrap();
+        for (field, _) in &accounts {
+            writeln!(code, "            \"{}\",", field).unwrap();
+        }
+        writeln!(code, "        ];").unwrap();
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "        pub fn to_account_metas(&self) -> Vec<AccountMeta> {{"
+        )
+        .unwrap();
+        writeln!(code, "            vec![").unwrap();
+        for (field, account) in &accounts {
+            let is_mut = flag(account, "isMut", "writable");
+            let is_signer = flag(account, "isSigner", "signer");
+            let meta = if is_optional(account) {
+                assert!(!is_signer, "optional signer {} in {}", field, name);
+                format!("optional_account_meta(self.{}, {})", field, is_mut)
+            } else if is_mut {
+                format!("AccountMeta::new(self.{}, {})", field, is_signer)
+            } else {
+                format!("AccountMeta::new_readonly(self.{}, {})", field, is_signer)
+            };
+            writeln!(code, "                {},", meta).unwrap();
+        }
+        writeln!(code, "            ]").unwrap();
+        writeln!(code, "        }}").unwrap();
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "        pub fn instruction(&self, args: &super::instruction::{}) -> Instruction {{",
+            struct_name
+        )
+        .unwrap();
+        writeln!(code, "            Instruction {{").unwrap();
+        writeln!(code, "                program_id: ID,").unwrap();
+        writeln!(code, "                accounts: self.to_account_metas(),").unwrap();
+        writeln!(code, "                data: args.data(),").unwrap();
+        writeln!(code, "            }}").unwrap();
+        writeln!(code, "        }}").unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    writeln!(code, "}}").unwrap();
+}
+
+fn generate_decode(code: &mut String, idl: &Value) {
+    writeln!(code).unwrap();
+    writeln!(
+        code,
+        "/// Identifies the instruction by its discriminator, None if unknown or if the args do not"
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "/// decode. Trailing bytes are ignored, as Anchor does."
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "pub fn decode(data: &[u8]) -> Option<IdlInstruction> {{"
+    )
+    .unwrap();
+    writeln!(code, "    if data.len() < 8 {{").unwrap();
+    writeln!(code, "        return None;").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(
+        code,
+        "    let discriminator: [u8; 8] = data[..8].try_into().unwrap();"
+    )
+    .unwrap();
+    writeln!(code, "    let args = &mut &data[8..];").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "    match discriminator {{").unwrap();
+    for ix in array(idl, "instructions") {
+        let struct_name = pascal_case(str_field(ix, "name"));
+        writeln!(
+            code,
+            "        instruction::{}::DISCRIMINATOR => Some(IdlInstruction {{",
+            struct_name
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "            name: instruction::{}::NAME,",
+            struct_name
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "            account_names: &accounts::{}::NAMES,",
+            struct_name
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "            args: format!(\"{{:?}}\", instruction::{}::deserialize(args).ok()?),",
+            struct_name
+        )
+        .unwrap();
+        writeln!(code, "        }}),").unwrap();
+    }
+    writeln!(code, "        _ => None,").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+}
+
+fn rust_type(ty: &Value) -> String {
+    if let Some(name) = ty.as_str() {
+        return match name {
+            "bool" | "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "u128"
+            | "i128" => name.to_string(),
+            "f32" | "f64" => name.to_string(),
+            "publicKey" | "pubkey" => "Pubkey".to_string(),
+            "string" => "String".to_string(),
+            "bytes" => "Vec<u8>".to_string(),
+            _ => panic!("unsupported IDL type {}", name),
+        };
+    }
+
+    if let Some(array) = ty.get("array").and_then(Value::as_array) {
+        format!("[{}; {}]", rust_type(&array[0]), array[1])
+    } else if let Some(inner) = ty.get("vec") {
+        format!("Vec<{}>", rust_type(inner))
+    } else if let Some(inner) = ty.get("option") {
+        format!("Option<{}>", rust_type(inner))
+    } else if let Some(defined) = ty.get("defined") {
+        // Anchor 0.30 nests the name in an object
+        defined
+            .as_str()
+            .unwrap_or_else(|| str_field(defined, "name"))
+            .to_string()
+    } else {
+        panic!("unsupported IDL type {}", ty)
+    }
+}
+
+/// Borsh fields of a struct or enum variant, named or tuple, with the `;` a tuple struct needs.
+fn struct_fields(fields: &[Value], indent: &str, is_struct: bool) -> String {
+    let visibility = if is_struct { "pub " } else { "" };
+    if fields.iter().all(|field| field.get("name").is_some()) {
+        let mut code = " {\n".to_string();
+        for field in fields {
+            writeln!(
+                code,
+                "{}    {}{}: {},",
+                indent,
+                visibility,
+                snake_case(str_field(field, "name")),
+                rust_type(&field["type"])
+            )
+            .unwrap();
+        }
+        code + indent + "}"
+    } else {
+        let types: Vec<String> = fields
+            .iter()
+            .map(|ty| format!("{}{}", visibility, rust_type(ty)))
+            .collect();
+        format!("({}){}", types.join(", "), if is_struct { ";" } else { "" })
+    }
+}
+
+/// Nested account groups are flattened in order, their accounts prefixed by the group name.
+fn flatten_accounts<'a>(accounts: &'a [Value], prefix: &str, flat: &mut Vec<(String, &'a Value)>) {
+    for account in accounts {
+        let field = format!("{}{}", prefix, snake_case(str_field(account, "name")));
+        match account.get("accounts").and_then(Value::as_array) {
+            Some(group) => flatten_accounts(group, &format!("{}_", field), flat),
+            None => flat.push((field, account)),
+        }
+    }
+}
+
+/// Anchor 0.30 IDLs carry the program address and discriminators, legacy ones don't.
+fn program_address(idl: &Value) -> &str {
+    idl["address"]
+        .as_str()
+        .unwrap_or_else(|| str_field(&idl["metadata"], "address"))
+}
+
+/// Anchor's instruction discriminator: sha256("global:<snake_case name>")[..8]
+fn discriminator(ix: &Value, name: &str) -> [u8; 8] {
+    if let Some(discriminator) = ix.get("discriminator").and_then(Value::as_array) {
+        let bytes: Vec<u8> = discriminator
+            .iter()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        return bytes
+            .try_into()
+            .unwrap_or_else(|_| panic!("{} discriminator is not 8 bytes", name));
+    }
+    let hash = Sha256::digest(format!("global:{}", name).as_bytes());
+    hash[..8].try_into().unwrap()
+}
+
+/// Account flag under its legacy name or its Anchor 0.30 name, false when absent.
+fn flag(account: &Value, legacy: &str, name: &str) -> bool {
+    account
+        .get(legacy)
+        .or_else(|| account.get(name))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+fn is_optional(account: &Value) -> bool {
+    flag(account, "isOptional", "optional")
+}
+
+fn array<'a>(value: &'a Value, field: &str) -> &'a Vec<Value> {
+    value[field]
+        .as_array()
+        .unwrap_or_else(|| panic!("missing array {} in {}", field, value))
+}
+
+fn str_field<'a>(value: &'a Value, field: &str) -> &'a str {
+    value[field]
+        .as_str()
+        .unwrap_or_else(|| panic!("missing string {} in {}", field, value))
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !snake.is_empty() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn pascal_case(name: &str) -> String {
+    snake_case(name)
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
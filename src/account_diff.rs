@@ -0,0 +1,218 @@
+use super::accounts::KaminoAccount;
+use fixed::types::U68F60 as Fraction;
+use serde_json::Value;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use std::fmt::Write;
+
+/// State of a set of accounts at one point, None for the ones that don't exist.
+#[derive(Clone, Debug)]
+pub struct AccountSnapshot {
+    pub accounts: Vec<(Pubkey, Option<Account>)>,
+}
+
+impl AccountSnapshot {
+    #[cfg(feature = "test-harness")]
+    pub async fn take(
+        context: &mut solana_program_test::ProgramTestContext,
+        addresses: &[Pubkey],
+    ) -> AccountSnapshot {
+        let mut accounts = vec![];
+        for address in addresses {
+            accounts.push((
+                *address,
+                crate::helper::get_account(&mut context.banks_client, *address).await,
+            ));
+        }
+
+        AccountSnapshot { accounts }
+    }
+
+    /// Changes from this snapshot to a later one of the same accounts.
+    pub fn diff(&self, after: &AccountSnapshot) -> StateDiff {
+        let accounts = self
+            .accounts
+            .iter()
+            .zip(after.accounts.iter())
+            .map(|((address, before), (after_address, after))| {
+                assert_eq!(address, after_address);
+                AccountDiff::new(address, before.as_ref(), after.as_ref())
+            })
+            .collect();
+
+        StateDiff { accounts }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Fraction,
+    pub after: Fraction,
+}
+
+impl FieldChange {
+    pub fn delta(&self) -> f64 {
+        self.after.to_num::<f64>() - self.before.to_num::<f64>()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountDiff {
+    pub address: Pubkey,
+    pub lamports_before: u64,
+    pub lamports_after: u64,
+    /// Amount held, for SPL token accounts
+    pub token_balance_before: Option<u64>,
+    pub token_balance_after: Option<u64>,
+    /// Decoded fields of klend and kfarm accounts that changed, named as in `inspect`
+    pub field_changes: Vec<FieldChange>,
+}
+
+impl AccountDiff {
+    pub fn new(address: &Pubkey, before: Option<&Account>, after: Option<&Account>) -> AccountDiff {
+        let before_fields = before.map(decode_fields).unwrap_or_default();
+        let after_fields = after.map(decode_fields).unwrap_or_default();
+
+        let mut field_changes = vec![];
+        for (field, before) in &before_fields {
+            let after = value_of(&after_fields, field);
+            if *before != after {
+                field_changes.push(FieldChange {
+                    field: field.clone(),
+                    before: *before,
+                    after,
+                });
+            }
+        }
+        for (field, after) in &after_fields {
+            if !before_fields.iter().any(|(name, _)| name == field) && *after != Fraction::ZERO {
+                field_changes.push(FieldChange {
+                    field: field.clone(),
+                    before: Fraction::ZERO,
+                    after: *after,
+                });
+            }
+        }
+
+        AccountDiff {
+            address: *address,
+            lamports_before: before.map_or(0, |account| account.lamports),
+            lamports_after: after.map_or(0, |account| account.lamports),
+            token_balance_before: before.and_then(token_balance),
+            token_balance_after: after.and_then(token_balance),
+            field_changes,
+        }
+    }
+
+    pub fn lamport_delta(&self) -> i128 {
+        self.lamports_after as i128 - self.lamports_before as i128
+    }
+
+    /// A token account created or closed by the transaction counts as holding 0.
+    pub fn token_delta(&self) -> i128 {
+        self.token_balance_after.unwrap_or(0) as i128
+            - self.token_balance_before.unwrap_or(0) as i128
+    }
+
+    pub fn field(&self, field: &str) -> Option<&FieldChange> {
+        self.field_changes
+            .iter()
+            .find(|change| change.field == field)
+    }
+}
+
+/// Per account changes made by a transaction, in the order the accounts were given.
+#[derive(Clone, Debug)]
+pub struct StateDiff {
+    pub accounts: Vec<AccountDiff>,
+}
+
+impl StateDiff {
+    pub fn account(&self, address: &Pubkey) -> &AccountDiff {
+        self.accounts
+            .iter()
+            .find(|diff| diff.address == *address)
+            .unwrap_or_else(|| panic!("account {} is not watched", address))
+    }
+
+    pub fn field(&self, address: &Pubkey, field: &str) -> Option<&FieldChange> {
+        self.account(address).field(field)
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut table = String::new();
+        for diff in &self.accounts {
+            writeln!(
+                table,
+                "{} lamports {:+}",
+                diff.address,
+                diff.lamport_delta()
+            )
+            .unwrap();
+            if diff.token_balance_before.is_some() || diff.token_balance_after.is_some() {
+                writeln!(
+                    table,
+                    "    {:<60} {:>24?} {:>24?} {:+}",
+                    "token balance",
+                    diff.token_balance_before,
+                    diff.token_balance_after,
+                    diff.token_delta()
+                )
+                .unwrap();
+            }
+            for change in &diff.field_changes {
+                writeln!(
+                    table,
+                    "    {:<60} {:>24} {:>24} {:+}",
+                    change.field,
+                    change.before.to_string(),
+                    change.after.to_string(),
+                    change.delta()
+                )
+                .unwrap();
+            }
+        }
+
+        table
+    }
+}
+
+fn token_balance(account: &Account) -> Option<u64> {
+    if account.owner != spl_token::id() || account.data.len() != spl_token::state::Account::LEN {
+        return None;
+    }
+
+    spl_token::state::Account::unpack_from_slice(&account.data)
+        .ok()
+        .map(|token_account| token_account.amount)
+}
+
+fn value_of(fields: &[(String, Fraction)], field: &str) -> Fraction {
+    fields
+        .iter()
+        .find(|(name, _)| name == field)
+        .map_or(Fraction::ZERO, |(_, value)| *value)
+}
+
+/// The numeric fields of klend and kfarm accounts, from the `inspect` field table where
+/// fractions are decimal strings.
+fn decode_fields(account: &Account) -> Vec<(String, Fraction)> {
+    let Some(decoded) = KaminoAccount::decode(&account.data) else {
+        return vec![];
+    };
+
+    decoded
+        .fields()
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let number = match &value {
+                Value::Number(number) => number.as_u64().map(Fraction::from),
+                Value::String(decimal) => Fraction::from_str(decimal).ok(),
+                _ => None,
+            };
+            number.map(|number| (name, number))
+        })
+        .collect()
+}
@@ -0,0 +1,425 @@
+//! Decodes klend and kfarm account dumps into named fields, for the `inspect` command, the
+//! test dumps and the state diffs.
+
+use crate::kfarm_state::FarmState;
+use crate::klend_math::{
+    big_fraction, collateral_exchange_rate, current_borrow_rate, total_supply, utilization_rate,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use borsh::BorshDeserialize;
+use fixed::types::U68F60 as Fraction;
+use klend::{LendingMarket, Obligation, Reserve};
+use serde_json::{json, Map, Value};
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// sha256("account:<name>")[..8]
+pub const LENDING_MARKET_DISCRIMINATOR: [u8; 8] = [246, 114, 50, 98, 72, 157, 28, 120];
+pub const RESERVE_DISCRIMINATOR: [u8; 8] = [43, 242, 204, 202, 26, 247, 59, 127];
+pub const OBLIGATION_DISCRIMINATOR: [u8; 8] = [168, 206, 141, 106, 88, 76, 172, 167];
+pub const FARM_STATE_DISCRIMINATOR: [u8; 8] = [198, 102, 216, 74, 63, 66, 163, 190];
+
+#[derive(Clone, Debug)]
+pub enum KaminoAccount {
+    LendingMarket(Box<LendingMarket>),
+    Reserve(Box<Reserve>),
+    Obligation(Box<Obligation>),
+    FarmState(Box<FarmState>),
+}
+
+impl KaminoAccount {
+    /// None for other accounts, picked by the anchor discriminator.
+    pub fn decode(data: &[u8]) -> Option<KaminoAccount> {
+        if data.len() < 8 {
+            return None;
+        }
+        let (discriminator, data) = data.split_at(8);
+
+        match discriminator {
+            d if d == LENDING_MARKET_DISCRIMINATOR => LendingMarket::try_from_slice(data)
+                .ok()
+                .map(|market| KaminoAccount::LendingMarket(Box::new(market))),
+            d if d == RESERVE_DISCRIMINATOR => Reserve::try_from_slice(data)
+                .ok()
+                .map(|reserve| KaminoAccount::Reserve(Box::new(reserve))),
+            d if d == OBLIGATION_DISCRIMINATOR => Obligation::try_from_slice(data)
+                .ok()
+                .map(|obligation| KaminoAccount::Obligation(Box::new(obligation))),
+            d if d == FARM_STATE_DISCRIMINATOR => FarmState::try_from_slice(data)
+                .ok()
+                .map(|farm| KaminoAccount::FarmState(Box::new(farm))),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            KaminoAccount::LendingMarket(_) => "LendingMarket",
+            KaminoAccount::Reserve(_) => "Reserve",
+            KaminoAccount::Obligation(_) => "Obligation",
+            KaminoAccount::FarmState(_) => "FarmState",
+        }
+    }
+
+    pub fn fields(&self) -> Vec<(String, Value)> {
+        match self {
+            KaminoAccount::LendingMarket(market) => lending_market_fields(market),
+            KaminoAccount::Reserve(reserve) => reserve_fields(reserve),
+            KaminoAccount::Obligation(obligation) => obligation_fields(obligation),
+            KaminoAccount::FarmState(farm) => farm_state_fields(farm),
+        }
+    }
+
+    /// Scaled fractions are strings, to keep their precision.
+    pub fn to_json(&self, address: Option<&Pubkey>) -> Value {
+        json!({
+            "address": address.map(|address| address.to_string()),
+            "type": self.type_name(),
+            "fields": self.fields().into_iter().collect::<Map<String, Value>>(),
+        })
+    }
+}
+
+impl fmt::Display for KaminoAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.type_name())?;
+        write!(f, "{}", format_fields(&self.fields()))
+    }
+}
+
+pub fn format_fields(fields: &[(String, Value)]) -> String {
+    fields
+        .iter()
+        .map(|(field, value)| match value {
+            Value::String(value) => format!("    {:<60} {}\n", field, value),
+            value => format!("    {:<60} {}\n", field, value),
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountDump {
+    /// From the JSON export, or the file name for raw dumps named after their address
+    pub address: Option<Pubkey>,
+    /// Only known for JSON exports
+    pub owner: Option<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+/// Reads the raw account data written by `solana account --output-file`, as in tests/fixtures,
+/// or the JSON of `solana account --output json`.
+pub fn read_account_dump(path: &Path) -> Result<AccountDump, String> {
+    let contents =
+        fs::read(path).map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+    let mut dump = parse_account_dump(&contents)?;
+
+    if dump.address.is_none() {
+        dump.address = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| Pubkey::from_str(stem).ok());
+    }
+
+    Ok(dump)
+}
+
+pub fn parse_account_dump(contents: &[u8]) -> Result<AccountDump, String> {
+    let export = serde_json::from_slice::<Value>(contents)
+        .ok()
+        .filter(|export| export["account"]["data"].is_array());
+
+    let Some(export) = export else {
+        return Ok(AccountDump {
+            address: None,
+            owner: None,
+            data: contents.to_vec(),
+        });
+    };
+
+    let pubkey = |value: &Value| value.as_str().and_then(|key| Pubkey::from_str(key).ok());
+    let data = &export["account"]["data"];
+    if data[1].as_str() != Some("base64") {
+        return Err("only base64 encoded exports are supported".to_string());
+    }
+
+    Ok(AccountDump {
+        address: pubkey(&export["pubkey"]),
+        owner: pubkey(&export["account"]["owner"]),
+        data: BASE64
+            .decode(data[0].as_str().unwrap_or_default())
+            .map_err(|e| format!("account data is not base64: {}", e))?,
+    })
+}
+
+pub fn lending_market_fields(market: &LendingMarket) -> Vec<(String, Value)> {
+    let mut fields = vec![
+        field("name", name(&market.name)),
+        field("version", market.version),
+        field(
+            "lending_market_owner",
+            market.lending_market_owner.to_string(),
+        ),
+        field("quote_currency", name(&market.quote_currency)),
+        field("referral_fee_bps", market.referral_fee_bps),
+        field("emergency_mode", market.emergency_mode),
+        field("borrow_disabled", market.borrow_disabled),
+        field(
+            "liquidation_max_debt_close_factor_pct",
+            market.liquidation_max_debt_close_factor_pct,
+        ),
+        field(
+            "insolvency_risk_unhealthy_ltv_pct",
+            market.insolvency_risk_unhealthy_ltv_pct,
+        ),
+        field(
+            "min_full_liquidation_value_threshold",
+            market.min_full_liquidation_value_threshold,
+        ),
+        field(
+            "max_liquidatable_debt_market_value_at_once",
+            market.max_liquidatable_debt_market_value_at_once,
+        ),
+        field(
+            "global_allowed_borrow_value",
+            market.global_allowed_borrow_value,
+        ),
+        field(
+            "global_unhealthy_borrow_value",
+            market.global_unhealthy_borrow_value,
+        ),
+        field(
+            "min_net_value_in_obligation_sf",
+            fraction(market.min_net_value_in_obligation_sf),
+        ),
+    ];
+
+    for group in &market.elevation_groups {
+        if group.id == 0 {
+            continue;
+        }
+        let prefix = format!("elevation_groups[{}]", group.id);
+        fields.push(field(&format!("{}.ltv_pct", prefix), group.ltv_pct));
+        fields.push(field(
+            &format!("{}.liquidation_threshold_pct", prefix),
+            group.liquidation_threshold_pct,
+        ));
+        fields.push(field(
+            &format!("{}.max_liquidation_bonus_bps", prefix),
+            group.max_liquidation_bonus_bps,
+        ));
+        fields.push(field(
+            &format!("{}.debt_reserve", prefix),
+            group.debt_reserve.to_string(),
+        ));
+    }
+
+    fields
+}
+
+pub fn reserve_fields(reserve: &Reserve) -> Vec<(String, Value)> {
+    vec![
+        field("lending_market", reserve.lending_market.to_string()),
+        field(
+            "config.token_info.name",
+            name(&reserve.config.token_info.name),
+        ),
+        field("last_update.slot", reserve.last_update.slot),
+        field(
+            "liquidity.mint_pubkey",
+            reserve.liquidity.mint_pubkey.to_string(),
+        ),
+        field("liquidity.mint_decimals", reserve.liquidity.mint_decimals),
+        field(
+            "liquidity.available_amount",
+            reserve.liquidity.available_amount,
+        ),
+        field(
+            "liquidity.borrowed_amount_sf",
+            fraction(reserve.liquidity.borrowed_amount_sf),
+        ),
+        field(
+            "liquidity.market_price_sf",
+            fraction(reserve.liquidity.market_price_sf),
+        ),
+        field(
+            "liquidity.market_price_last_updated_ts",
+            reserve.liquidity.market_price_last_updated_ts,
+        ),
+        field(
+            "liquidity.cumulative_borrow_rate_bsf",
+            big_fraction(&reserve.liquidity.cumulative_borrow_rate_bsf).to_string(),
+        ),
+        field(
+            "liquidity.accumulated_protocol_fees_sf",
+            fraction(reserve.liquidity.accumulated_protocol_fees_sf),
+        ),
+        field(
+            "liquidity.accumulated_referrer_fees_sf",
+            fraction(reserve.liquidity.accumulated_referrer_fees_sf),
+        ),
+        field(
+            "collateral.mint_total_supply",
+            reserve.collateral.mint_total_supply,
+        ),
+        field("config.loan_to_value_pct", reserve.config.loan_to_value_pct),
+        field(
+            "config.liquidation_threshold_pct",
+            reserve.config.liquidation_threshold_pct,
+        ),
+        field("config.deposit_limit", reserve.config.deposit_limit),
+        field("config.borrow_limit", reserve.config.borrow_limit),
+        field("farm_collateral", reserve.farm_collateral.to_string()),
+        field("farm_debt", reserve.farm_debt.to_string()),
+        field("total_supply", total_supply(reserve).to_string()),
+        field("utilization_rate", utilization_rate(reserve).to_string()),
+        field("borrow_rate", current_borrow_rate(reserve).to_string()),
+        field(
+            "collateral_exchange_rate",
+            collateral_exchange_rate(reserve).to_string(),
+        ),
+    ]
+}
+
+/// Deposits and borrows are named after their reserve, as their position in the obligation
+/// shifts when one is removed.
+pub fn obligation_fields(obligation: &Obligation) -> Vec<(String, Value)> {
+    let mut fields = vec![
+        field("lending_market", obligation.lending_market.to_string()),
+        field("owner", obligation.owner.to_string()),
+        field("last_update.slot", obligation.last_update.slot),
+        field("elevation_group", obligation.elevation_group),
+        field("has_debt", obligation.has_debt),
+        field(
+            "deposited_value_sf",
+            fraction(obligation.deposited_value_sf),
+        ),
+        field(
+            "borrowed_assets_market_value_sf",
+            fraction(obligation.borrowed_assets_market_value_sf),
+        ),
+        field(
+            "borrow_factor_adjusted_debt_value_sf",
+            fraction(obligation.borrow_factor_adjusted_debt_value_sf),
+        ),
+        field(
+            "allowed_borrow_value_sf",
+            fraction(obligation.allowed_borrow_value_sf),
+        ),
+        field(
+            "unhealthy_borrow_value_sf",
+            fraction(obligation.unhealthy_borrow_value_sf),
+        ),
+    ];
+
+    for deposit in &obligation.deposits {
+        if deposit.deposit_reserve == Pubkey::default() {
+            continue;
+        }
+        let prefix = format!("deposits[{}]", deposit.deposit_reserve);
+        fields.push(field(
+            &format!("{}.deposited_amount", prefix),
+            deposit.deposited_amount,
+        ));
+        fields.push(field(
+            &format!("{}.market_value_sf", prefix),
+            fraction(deposit.market_value_sf),
+        ));
+    }
+
+    for borrow in &obligation.borrows {
+        if borrow.borrow_reserve == Pubkey::default() {
+            continue;
+        }
+        let prefix = format!("borrows[{}]", borrow.borrow_reserve);
+        fields.push(field(
+            &format!("{}.borrowed_amount_sf", prefix),
+            fraction(borrow.borrowed_amount_sf),
+        ));
+        fields.push(field(
+            &format!("{}.market_value_sf", prefix),
+            fraction(borrow.market_value_sf),
+        ));
+        fields.push(field(
+            &format!("{}.cumulative_borrow_rate_bsf", prefix),
+            big_fraction(&borrow.cumulative_borrow_rate_bsf).to_string(),
+        ));
+    }
+
+    fields
+}
+
+pub fn farm_state_fields(farm: &FarmState) -> Vec<(String, Value)> {
+    let mut fields = vec![
+        field("farm_admin", farm.farm_admin.to_string()),
+        field("global_config", farm.global_config.to_string()),
+        field("token.mint", farm.token.mint.to_string()),
+        field("token.decimals", farm.token.decimals),
+        field("is_farm_delegated", farm.is_farm_delegated),
+        field("is_farm_frozen", farm.is_farm_frozen),
+        field("delegate_authority", farm.delegate_authority.to_string()),
+        field("farm_vault", farm.farm_vault.to_string()),
+        field("num_users", farm.num_users),
+        field("total_staked_amount", farm.total_staked_amount),
+        field("deposit_cap_amount", farm.deposit_cap_amount),
+        field("deposit_warmup_period", farm.deposit_warmup_period),
+        field(
+            "withdrawal_cooldown_period",
+            farm.withdrawal_cooldown_period,
+        ),
+        field("num_reward_tokens", farm.num_reward_tokens),
+    ];
+
+    for (i, reward) in farm
+        .reward_infos
+        .iter()
+        .take(farm.num_reward_tokens as usize)
+        .enumerate()
+    {
+        let prefix = format!("reward_infos[{}]", i);
+        fields.push(field(
+            &format!("{}.token.mint", prefix),
+            reward.token.mint.to_string(),
+        ));
+        fields.push(field(
+            &format!("{}.rewards_vault", prefix),
+            reward.rewards_vault.to_string(),
+        ));
+        fields.push(field(
+            &format!("{}.rewards_available", prefix),
+            reward.rewards_available,
+        ));
+        fields.push(field(
+            &format!("{}.rewards_issued_cumulative", prefix),
+            reward.rewards_issued_cumulative,
+        ));
+        fields.push(field(
+            &format!("{}.last_issuance_ts", prefix),
+            reward.last_issuance_ts,
+        ));
+        fields.push(field(
+            &format!("{}.reward_per_share_scaled", prefix),
+            reward.reward_per_share_scaled.to_string(),
+        ));
+    }
+
+    fields
+}
+
+fn field(name: &str, value: impl Into<Value>) -> (String, Value) {
+    (name.to_string(), value.into())
+}
+
+fn fraction(sf: u128) -> String {
+    Fraction::from_bits(sf).to_string()
+}
+
+// Zero padded utf8, like market and token names
+fn name(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .to_string()
+}
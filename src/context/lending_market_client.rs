@@ -0,0 +1,1117 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::helper::{
+    create_token_account, get_account, get_associated_token_address,
+    get_or_create_associated_token_address, get_sysvar_clock, get_token_balance,
+    process_instructions, try_process_instructions,
+};
+use crate::kamino::{
+    compose_klend_borrow_obligation_liquidity_ix, compose_klend_deposit_obligation_collateral_ix,
+    compose_klend_deposit_reserve_liquidity_and_obligation_collateral_ix,
+    compose_klend_deposit_reserve_liquidity_ix, compose_klend_init_obligation_farms_for_reserve_ix,
+    compose_klend_init_obligation_ix, compose_klend_init_referrer_token_state_ix,
+    compose_klend_liquidate_obligation_and_redeem_reserve_collateral_ix,
+    compose_klend_redeem_reserve_collateral_ix, compose_klend_refresh_obligation_ix,
+    compose_klend_refresh_reserve_ix, compose_klend_repay_obligation_liquidity_ix,
+    compose_klend_request_elevation_group_ix, compose_klend_socialize_loss_ix,
+    compose_klend_withdraw_obligation_collateral_and_redeem_reserve_collateral_ix,
+    compose_klend_withdraw_obligation_collateral_ix, compose_klend_withdraw_referrer_fees_ix,
+    lending_market_authority, obligation_farm_user_state, Asset, FarmMode, KFARM_PROGRAM_ID,
+    KLEND_PROGRAM_ID,
+};
+use crate::klend_math::accrue_interest;
+use crate::lookup_table::extend_table;
+use crate::obligation_health::ObligationHealth;
+use crate::reserve_registry::{ReserveInfo, ReserveRegistry};
+use borsh::{BorshDeserialize, BorshSerialize};
+use klend::{LendingMarket, Obligation, Reserve};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signer;
+use solana_sdk::transport::TransportError;
+use spl_token;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// klend user operations against a single lending market, for any registered reserve of it.
+pub struct LendingMarketClient {
+    pub context: Rc<RefCell<ProgramTestContext>>,
+    pub registry: Rc<ReserveRegistry>,
+    pub market: Pubkey,
+    pub market_authority: Pubkey,
+    /// Obligations initialized in this session, shared by every client of the test context
+    pub obligations: Rc<RefCell<Vec<Pubkey>>>,
+}
+
+impl LendingMarketClient {
+    pub fn new(
+        context: Rc<RefCell<ProgramTestContext>>,
+        registry: Rc<ReserveRegistry>,
+        market: &Pubkey,
+        obligations: Rc<RefCell<Vec<Pubkey>>>,
+    ) -> LendingMarketClient {
+        LendingMarketClient {
+            context,
+            registry,
+            market: *market,
+            market_authority: lending_market_authority(market),
+            obligations,
+        }
+    }
+
+    pub fn reserve(&self, asset: Asset) -> &ReserveInfo {
+        let info = self.registry.asset(asset);
+        assert_eq!(
+            info.lending_market, self.market,
+            "reserve {} does not belong to market {}",
+            info.address, self.market
+        );
+
+        info
+    }
+
+    pub fn reserves(&self) -> Vec<Pubkey> {
+        self.registry
+            .market_reserves(&self.market)
+            .map(|r| r.address)
+            .collect()
+    }
+
+    /// Market, reserve and program accounts shared by every klend transaction on this market.
+    pub fn lookup_table_addresses(&self) -> Vec<Pubkey> {
+        let mut addresses = vec![
+            self.market,
+            self.market_authority,
+            KLEND_PROGRAM_ID,
+            KFARM_PROGRAM_ID,
+            spl_token::id(),
+            sysvar::instructions::id(),
+            sysvar::rent::id(),
+            system_program::id(),
+        ];
+        for reserve in self.registry.market_reserves(&self.market) {
+            addresses.extend([
+                reserve.address,
+                reserve.liquidity_mint,
+                reserve.liquidity_supply_vault,
+                reserve.liquidity_fee_vault,
+                reserve.collateral_mint,
+                reserve.collateral_supply_vault,
+                reserve.scope_prices,
+            ]);
+            addresses.extend(
+                [reserve.farm_collateral, reserve.farm_debt]
+                    .into_iter()
+                    .filter(|farm| *farm != Pubkey::default()),
+            );
+        }
+
+        // Reserves share their oracle accounts
+        let mut unique: Vec<Pubkey> = vec![];
+        for address in addresses {
+            if !unique.contains(&address) {
+                unique.push(address);
+            }
+        }
+
+        unique
+    }
+
+    /// Adds the market accounts to a lookup table of `authority`, e.g. its user lookup table.
+    pub async fn extend_lookup_table(&self, authority: &Keypair, table: &Pubkey) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        extend_table(context, authority, table, &self.lookup_table_addresses()).await;
+    }
+
+    /// The reserve state projected to the current slot.
+    pub async fn get_reserve(&self, asset: Asset) -> Reserve {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        read_projected_reserve(context, &self.reserve(asset).address).await
+    }
+
+    /// The reserve account as last refreshed on chain.
+    pub async fn get_reserve_account(&self, asset: Asset) -> Reserve {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        read_reserve(context, &self.reserve(asset).address).await
+    }
+
+    pub async fn get_lending_market(&self) -> LendingMarket {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        read_lending_market(context, &self.market).await
+    }
+
+    pub async fn get_obligation(&self, obligation: &Pubkey) -> Obligation {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        read_obligation(context, obligation).await
+    }
+
+    /// Overwrites the obligation account, for states no instruction sequence reaches in a test.
+    pub async fn set_obligation(&self, obligation: &Pubkey, state: &Obligation) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        write_klend_account(context, obligation, state.try_to_vec().unwrap()).await;
+    }
+
+    pub async fn set_reserve(&self, asset: Asset, state: &Reserve) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        write_klend_account(
+            context,
+            &self.reserve(asset).address,
+            state.try_to_vec().unwrap(),
+        )
+        .await;
+    }
+
+    pub async fn set_lending_market(&self, state: &LendingMarket) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        write_klend_account(context, &self.market, state.try_to_vec().unwrap()).await;
+    }
+
+    /// Health of the obligation at the reserve states projected to the current slot.
+    pub async fn obligation_health(&self, obligation: &Pubkey) -> ObligationHealth {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let state = read_obligation(context, obligation).await;
+        let market = read_lending_market(context, &self.market).await;
+        let mut reserves = HashMap::new();
+        for reserve in self.reserves() {
+            reserves.insert(reserve, read_projected_reserve(context, &reserve).await);
+        }
+
+        ObligationHealth::new(&state, &market, &reserves)
+    }
+
+    /// klend closes an obligation once it has no deposit and no borrow left.
+    pub async fn obligation_exists(&self, obligation: &Pubkey) -> bool {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        get_account(&mut context.banks_client, *obligation)
+            .await
+            .is_some()
+    }
+
+    pub async fn init_obligation(&self, user: &Keypair, tag: u8, id: u8) -> Pubkey {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let seed_account = Pubkey::default();
+        let (obligation, _) = Pubkey::find_program_address(
+            &[
+                &[tag],
+                &[id],
+                &user.pubkey().to_bytes(),
+                &self.market.to_bytes(),
+                &seed_account.to_bytes(),
+                &seed_account.to_bytes(),
+            ],
+            &KLEND_PROGRAM_ID,
+        );
+
+        let (user_metadata, _) = Pubkey::find_program_address(
+            &[b"user_meta", &user.pubkey().to_bytes()],
+            &KLEND_PROGRAM_ID,
+        );
+
+        let instruction = compose_klend_init_obligation_ix(
+            &user.pubkey(),
+            &user.pubkey(),
+            &obligation,
+            &self.market,
+            &seed_account,
+            &seed_account,
+            &user_metadata,
+            tag,
+            id,
+        );
+
+        process_instructions(context, user, &[instruction]).await;
+
+        let mut obligations = self.obligations.borrow_mut();
+        if !obligations.contains(&obligation) {
+            obligations.push(obligation);
+        }
+
+        obligation
+    }
+
+    pub async fn refresh_reserve(&self, user: &Keypair, asset: Asset) {
+        self.try_refresh_reserve(user, asset).await.unwrap();
+    }
+
+    pub async fn try_refresh_reserve(
+        &self,
+        user: &Keypair,
+        asset: Asset,
+    ) -> Result<(), TransportError> {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let instruction = compose_klend_refresh_reserve_ix(reserve);
+
+        try_process_instructions(context, user, &[instruction]).await
+    }
+
+    pub async fn refresh_obligation(&self, user: &Keypair, obligation: &Pubkey) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let instructions = self.compose_refresh_ixs(context, obligation, None).await;
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    pub async fn deposit_reserve_liquidity(
+        &self,
+        user: &Keypair,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let user_destination_collateral = get_or_create_associated_token_address(
+            context,
+            user,
+            &user.pubkey(),
+            &reserve.collateral_mint,
+        )
+        .await;
+
+        let mut instructions: Vec<Instruction> = vec![];
+        instructions.push(compose_klend_refresh_reserve_ix(reserve));
+
+        if is_native(reserve) {
+            let user_wsol_acc = Keypair::new();
+            create_token_account(
+                context,
+                user,
+                &user_wsol_acc,
+                &spl_token::native_mint::id(),
+                &user.pubkey(),
+                liquidity_amount,
+            )
+            .await
+            .unwrap();
+
+            instructions.push(self.compose_deposit_reserve_liquidity_ix(
+                user,
+                reserve,
+                &user_wsol_acc.pubkey(),
+                &user_destination_collateral,
+                liquidity_amount,
+            ));
+            instructions.push(compose_close_account_ix(user, &user_wsol_acc.pubkey()));
+        } else {
+            let user_source_liquidity = get_or_create_associated_token_address(
+                context,
+                user,
+                &user.pubkey(),
+                &reserve.liquidity_mint,
+            )
+            .await;
+
+            instructions.push(self.compose_deposit_reserve_liquidity_ix(
+                user,
+                reserve,
+                &user_source_liquidity,
+                &user_destination_collateral,
+                liquidity_amount,
+            ));
+        }
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    /// Redeems the whole collateral token balance of the user.
+    pub async fn redeem_reserve_collateral(&self, user: &Keypair, asset: Asset) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let user_destination_liquidity = get_or_create_associated_token_address(
+            context,
+            user,
+            &user.pubkey(),
+            &reserve.liquidity_mint,
+        )
+        .await;
+
+        let user_source_collateral = get_or_create_associated_token_address(
+            context,
+            user,
+            &user.pubkey(),
+            &reserve.collateral_mint,
+        )
+        .await;
+
+        let collateral_amount =
+            get_token_balance(&mut context.banks_client, user_source_collateral).await;
+
+        let mut instructions: Vec<Instruction> = vec![];
+        instructions.push(compose_klend_refresh_reserve_ix(reserve));
+
+        instructions.push(compose_klend_redeem_reserve_collateral_ix(
+            &user.pubkey(),
+            &reserve.address,
+            &self.market,
+            &self.market_authority,
+            &reserve.liquidity_mint,
+            &reserve.liquidity_supply_vault,
+            &reserve.collateral_mint,
+            &user_source_collateral,
+            &user_destination_liquidity,
+            collateral_amount,
+        ));
+
+        if is_native(reserve) {
+            instructions.push(compose_close_account_ix(user, &user_destination_liquidity));
+        }
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    /// Deposits the whole collateral token balance of the user into the obligation.
+    pub async fn deposit_obligation_collateral(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let user_source_collateral =
+            get_associated_token_address(&user.pubkey(), &reserve.collateral_mint).await;
+        let collateral_amount =
+            get_token_balance(&mut context.banks_client, user_source_collateral).await;
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+
+        instructions.push(compose_klend_deposit_obligation_collateral_ix(
+            &user.pubkey(),
+            obligation,
+            &self.market,
+            &reserve.address,
+            &reserve.collateral_supply_vault,
+            &user_source_collateral,
+            collateral_amount,
+        ));
+
+        process_instructions(context, user, &instructions).await;
+
+        let collateral_amount =
+            get_token_balance(&mut context.banks_client, user_source_collateral).await;
+        assert_eq!(collateral_amount, 0);
+    }
+
+    /// Withdraws collateral tokens from the obligation, u64::MAX withdraws everything.
+    pub async fn withdraw_obligation_collateral(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        collateral_amount: u64,
+    ) {
+        self.try_withdraw_obligation_collateral(user, obligation, asset, collateral_amount)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_withdraw_obligation_collateral(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        collateral_amount: u64,
+    ) -> Result<(), TransportError> {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let user_destination_collateral = get_or_create_associated_token_address(
+            context,
+            user,
+            &user.pubkey(),
+            &reserve.collateral_mint,
+        )
+        .await;
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+
+        instructions.push(compose_klend_withdraw_obligation_collateral_ix(
+            &user.pubkey(),
+            obligation,
+            &self.market,
+            &self.market_authority,
+            &reserve.address,
+            &reserve.collateral_supply_vault,
+            &user_destination_collateral,
+            collateral_amount,
+        ));
+
+        try_process_instructions(context, user, &instructions).await
+    }
+
+    pub async fn borrow_obligation_liquidity(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) {
+        self.try_borrow_obligation_liquidity(user, obligation, asset, liquidity_amount)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_borrow_obligation_liquidity(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) -> Result<(), TransportError> {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let user_destination_liquidity = get_or_create_associated_token_address(
+            context,
+            user,
+            &user.pubkey(),
+            &reserve.liquidity_mint,
+        )
+        .await;
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+
+        instructions.push(compose_klend_borrow_obligation_liquidity_ix(
+            &user.pubkey(),
+            obligation,
+            &self.market,
+            &self.market_authority,
+            &reserve.address,
+            &reserve.liquidity_mint,
+            &reserve.liquidity_supply_vault,
+            &reserve.liquidity_fee_vault,
+            &user_destination_liquidity,
+            liquidity_amount,
+        ));
+        let obligation_state = read_obligation(context, obligation).await;
+        extend_elevation_group_accounts(instructions.last_mut().unwrap(), &obligation_state);
+
+        if is_native(reserve) {
+            instructions.push(compose_close_account_ix(user, &user_destination_liquidity));
+        }
+
+        try_process_instructions(context, user, &instructions).await
+    }
+
+    pub async fn repay_obligation_liquidity(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+        let obligation_state = read_obligation(context, obligation).await;
+
+        if is_native(reserve) {
+            let user_wsol_acc = Keypair::new();
+            create_token_account(
+                context,
+                user,
+                &user_wsol_acc,
+                &spl_token::native_mint::id(),
+                &user.pubkey(),
+                liquidity_amount,
+            )
+            .await
+            .unwrap();
+
+            instructions.push(self.compose_repay_obligation_liquidity_ix(
+                user,
+                obligation,
+                reserve,
+                &user_wsol_acc.pubkey(),
+                liquidity_amount,
+            ));
+            extend_elevation_group_accounts(instructions.last_mut().unwrap(), &obligation_state);
+            instructions.push(compose_close_account_ix(user, &user_wsol_acc.pubkey()));
+        } else {
+            let user_source_liquidity = get_or_create_associated_token_address(
+                context,
+                user,
+                &user.pubkey(),
+                &reserve.liquidity_mint,
+            )
+            .await;
+
+            instructions.push(self.compose_repay_obligation_liquidity_ix(
+                user,
+                obligation,
+                reserve,
+                &user_source_liquidity,
+                liquidity_amount,
+            ));
+            extend_elevation_group_accounts(instructions.last_mut().unwrap(), &obligation_state);
+        }
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    /// Repays up to `liquidity_amount` of `repay` debt of an unhealthy obligation and receives its
+    /// `withdraw` collateral with the liquidation bonus, redeemed to the liquidator liquidity ATA.
+    pub async fn liquidate_obligation_and_redeem_reserve_collateral(
+        &self,
+        liquidator: &Keypair,
+        obligation: &Pubkey,
+        repay: Asset,
+        withdraw: Asset,
+        liquidity_amount: u64,
+        min_acceptable_received_liquidity_amount: u64,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let repay_reserve = self.reserve(repay);
+        let withdraw_reserve = self.reserve(withdraw);
+        assert!(
+            !is_native(withdraw_reserve),
+            "wsol collateral is not supported"
+        );
+
+        let user_destination_collateral = get_or_create_associated_token_address(
+            context,
+            liquidator,
+            &liquidator.pubkey(),
+            &withdraw_reserve.collateral_mint,
+        )
+        .await;
+        let user_destination_liquidity = get_or_create_associated_token_address(
+            context,
+            liquidator,
+            &liquidator.pubkey(),
+            &withdraw_reserve.liquidity_mint,
+        )
+        .await;
+
+        let user_wsol_acc = Keypair::new();
+        let user_source_liquidity = if is_native(repay_reserve) {
+            create_token_account(
+                context,
+                liquidator,
+                &user_wsol_acc,
+                &spl_token::native_mint::id(),
+                &liquidator.pubkey(),
+                liquidity_amount,
+            )
+            .await
+            .unwrap();
+            user_wsol_acc.pubkey()
+        } else {
+            get_or_create_associated_token_address(
+                context,
+                liquidator,
+                &liquidator.pubkey(),
+                &repay_reserve.liquidity_mint,
+            )
+            .await
+        };
+
+        let mut instructions = self.compose_refresh_ixs(context, obligation, None).await;
+
+        instructions.push(
+            compose_klend_liquidate_obligation_and_redeem_reserve_collateral_ix(
+                &liquidator.pubkey(),
+                obligation,
+                &self.market,
+                &self.market_authority,
+                &repay_reserve.address,
+                &repay_reserve.liquidity_mint,
+                &repay_reserve.liquidity_supply_vault,
+                &withdraw_reserve.address,
+                &withdraw_reserve.liquidity_mint,
+                &withdraw_reserve.collateral_mint,
+                &withdraw_reserve.collateral_supply_vault,
+                &withdraw_reserve.liquidity_supply_vault,
+                &withdraw_reserve.liquidity_fee_vault,
+                &user_source_liquidity,
+                &user_destination_collateral,
+                &user_destination_liquidity,
+                liquidity_amount,
+                min_acceptable_received_liquidity_amount,
+                0,
+            ),
+        );
+
+        if is_native(repay_reserve) {
+            instructions.push(compose_close_account_ix(
+                liquidator,
+                &user_wsol_acc.pubkey(),
+            ));
+        }
+
+        process_instructions(context, liquidator, &instructions).await;
+    }
+
+    pub async fn init_obligation_farms_for_reserve(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        mode: FarmMode,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let reserve_farm_state = reserve.farm(mode);
+        assert_ne!(
+            reserve_farm_state,
+            Pubkey::default(),
+            "reserve {} has no {:?} farm",
+            reserve.symbol,
+            mode
+        );
+
+        let obligation_farm = obligation_farm_user_state(&reserve_farm_state, obligation);
+
+        let instruction = compose_klend_init_obligation_farms_for_reserve_ix(
+            &user.pubkey(),
+            &user.pubkey(),
+            obligation,
+            &self.market_authority,
+            &reserve.address,
+            &reserve_farm_state,
+            &obligation_farm,
+            &self.market,
+            mode as u8,
+        );
+
+        process_instructions(context, user, &[instruction]).await;
+    }
+
+    /// Deposits liquidity and its collateral tokens into the obligation in one instruction. The
+    /// reserve must have no collateral farm, see `assert_no_farm_collateral`.
+    pub async fn deposit_reserve_liquidity_and_obligation_collateral(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+        assert_no_farm_collateral(reserve);
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+
+        let user_source_liquidity = if is_native(reserve) {
+            let user_wsol_acc = Keypair::new();
+            create_token_account(
+                context,
+                user,
+                &user_wsol_acc,
+                &spl_token::native_mint::id(),
+                &user.pubkey(),
+                liquidity_amount,
+            )
+            .await
+            .unwrap();
+
+            user_wsol_acc.pubkey()
+        } else {
+            get_or_create_associated_token_address(
+                context,
+                user,
+                &user.pubkey(),
+                &reserve.liquidity_mint,
+            )
+            .await
+        };
+
+        instructions.push(
+            compose_klend_deposit_reserve_liquidity_and_obligation_collateral_ix(
+                &user.pubkey(),
+                obligation,
+                &self.market,
+                &self.market_authority,
+                &reserve.address,
+                &reserve.liquidity_mint,
+                &reserve.liquidity_supply_vault,
+                &reserve.collateral_mint,
+                &reserve.collateral_supply_vault,
+                &user_source_liquidity,
+                liquidity_amount,
+            ),
+        );
+
+        if is_native(reserve) {
+            instructions.push(compose_close_account_ix(user, &user_source_liquidity));
+        }
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    /// Withdraws collateral tokens from the obligation and redeems them to the user liquidity in
+    /// one instruction, u64::MAX withdraws everything. klend closes the obligation if it ends up
+    /// empty. The reserve must have no collateral farm, see `assert_no_farm_collateral`.
+    pub async fn withdraw_obligation_collateral_and_redeem_reserve_collateral(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        collateral_amount: u64,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+        assert_no_farm_collateral(reserve);
+
+        let user_destination_liquidity = get_or_create_associated_token_address(
+            context,
+            user,
+            &user.pubkey(),
+            &reserve.liquidity_mint,
+        )
+        .await;
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+
+        instructions.push(
+            compose_klend_withdraw_obligation_collateral_and_redeem_reserve_collateral_ix(
+                &user.pubkey(),
+                obligation,
+                &self.market,
+                &self.market_authority,
+                &reserve.address,
+                &reserve.liquidity_mint,
+                &reserve.collateral_supply_vault,
+                &reserve.collateral_mint,
+                &reserve.liquidity_supply_vault,
+                &user_destination_liquidity,
+                collateral_amount,
+            ),
+        );
+
+        if is_native(reserve) {
+            instructions.push(compose_close_account_ix(user, &user_destination_liquidity));
+        }
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    /// Moves the obligation into an elevation group of the market, 0 leaves it.
+    pub async fn request_elevation_group(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        elevation_group: u8,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let obligation_reserves = obligation_reserves(&read_obligation(context, obligation).await);
+        let mut instructions =
+            self.compose_refresh_obligation_ixs(obligation, &obligation_reserves, None);
+        instructions.push(compose_klend_request_elevation_group_ix(
+            &user.pubkey(),
+            obligation,
+            &self.market,
+            &obligation_reserves,
+            elevation_group,
+        ));
+
+        process_instructions(context, user, &instructions).await;
+    }
+
+    pub async fn init_referrer_token_state(
+        &self,
+        payer: &Keypair,
+        referrer: &Pubkey,
+        asset: Asset,
+    ) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let instruction = compose_klend_init_referrer_token_state_ix(
+            &payer.pubkey(),
+            &self.market,
+            &self.reserve(asset).address,
+            referrer,
+        );
+
+        process_instructions(context, payer, &[instruction]).await;
+    }
+
+    /// Sends the unclaimed referral fees of the reserve to the referrer liquidity ATA.
+    pub async fn withdraw_referrer_fees(&self, referrer: &Keypair, asset: Asset) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let referrer_token_account = get_or_create_associated_token_address(
+            context,
+            referrer,
+            &referrer.pubkey(),
+            &reserve.liquidity_mint,
+        )
+        .await;
+
+        let instructions = vec![
+            compose_klend_refresh_reserve_ix(reserve),
+            compose_klend_withdraw_referrer_fees_ix(
+                &referrer.pubkey(),
+                &reserve.address,
+                &reserve.liquidity_mint,
+                &reserve.liquidity_supply_vault,
+                &referrer_token_account,
+                &self.market,
+                &self.market_authority,
+            ),
+        ];
+
+        process_instructions(context, referrer, &instructions).await;
+    }
+
+    pub async fn socialize_loss(
+        &self,
+        risk_council: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) {
+        self.try_socialize_loss(risk_council, obligation, asset, liquidity_amount)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_socialize_loss(
+        &self,
+        risk_council: &Keypair,
+        obligation: &Pubkey,
+        asset: Asset,
+        liquidity_amount: u64,
+    ) -> Result<(), TransportError> {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let reserve = self.reserve(asset);
+
+        let mut instructions = self
+            .compose_refresh_ixs(context, obligation, Some(&reserve.address))
+            .await;
+        instructions.push(compose_klend_socialize_loss_ix(
+            &risk_council.pubkey(),
+            obligation,
+            &self.market,
+            &reserve.address,
+            liquidity_amount,
+        ));
+
+        try_process_instructions(context, risk_council, &instructions).await
+    }
+
+    /// Refreshes every reserve the obligation uses (`reserve` last), then the obligation itself.
+    async fn compose_refresh_ixs(
+        &self,
+        context: &mut ProgramTestContext,
+        obligation: &Pubkey,
+        reserve: Option<&Pubkey>,
+    ) -> Vec<Instruction> {
+        let obligation_reserves = obligation_reserves(&read_obligation(context, obligation).await);
+
+        self.compose_refresh_obligation_ixs(obligation, &obligation_reserves, reserve)
+    }
+
+    /// Same as `compose_refresh_ixs` for the reserves the obligation will have at that point of a
+    /// transaction, deposits first then borrows.
+    pub fn compose_refresh_obligation_ixs(
+        &self,
+        obligation: &Pubkey,
+        obligation_reserves: &[Pubkey],
+        reserve: Option<&Pubkey>,
+    ) -> Vec<Instruction> {
+        // klend checks that the reserve being operated on is refreshed right before the obligation
+        let mut refreshed = obligation_reserves.to_vec();
+        if let Some(reserve) = reserve {
+            refreshed.retain(|r| r != reserve);
+            refreshed.push(*reserve);
+        }
+
+        let mut instructions: Vec<Instruction> = refreshed
+            .iter()
+            .map(|r| compose_klend_refresh_reserve_ix(self.registry.get(r)))
+            .collect();
+
+        instructions.push(compose_klend_refresh_obligation_ix(
+            obligation,
+            &self.market,
+            obligation_reserves,
+        ));
+
+        instructions
+    }
+
+    fn compose_deposit_reserve_liquidity_ix(
+        &self,
+        user: &Keypair,
+        reserve: &ReserveInfo,
+        user_source_liquidity: &Pubkey,
+        user_destination_collateral: &Pubkey,
+        liquidity_amount: u64,
+    ) -> Instruction {
+        compose_klend_deposit_reserve_liquidity_ix(
+            &user.pubkey(),
+            &reserve.address,
+            &self.market,
+            &self.market_authority,
+            &reserve.liquidity_mint,
+            &reserve.liquidity_supply_vault,
+            &reserve.collateral_mint,
+            user_source_liquidity,
+            user_destination_collateral,
+            liquidity_amount,
+        )
+    }
+
+    fn compose_repay_obligation_liquidity_ix(
+        &self,
+        user: &Keypair,
+        obligation: &Pubkey,
+        reserve: &ReserveInfo,
+        user_source_liquidity: &Pubkey,
+        liquidity_amount: u64,
+    ) -> Instruction {
+        compose_klend_repay_obligation_liquidity_ix(
+            &user.pubkey(),
+            obligation,
+            &self.market,
+            &reserve.address,
+            &reserve.liquidity_mint,
+            &reserve.liquidity_supply_vault,
+            user_source_liquidity,
+            liquidity_amount,
+        )
+    }
+}
+
+pub async fn read_obligation(context: &mut ProgramTestContext, obligation: &Pubkey) -> Obligation {
+    let account = get_account(&mut context.banks_client, *obligation)
+        .await
+        .unwrap();
+
+    Obligation::try_from_slice(&account.data[8..]).unwrap()
+}
+
+pub async fn read_reserve(context: &mut ProgramTestContext, reserve: &Pubkey) -> Reserve {
+    let account = get_account(&mut context.banks_client, *reserve)
+        .await
+        .unwrap();
+
+    Reserve::try_from_slice(&account.data[8..]).unwrap()
+}
+
+/// The reserve as refresh_reserve would leave it in the current slot.
+pub async fn read_projected_reserve(context: &mut ProgramTestContext, reserve: &Pubkey) -> Reserve {
+    let mut state = read_reserve(context, reserve).await;
+    let market = read_lending_market(context, &state.lending_market).await;
+    let clock = get_sysvar_clock(&mut context.banks_client).await;
+
+    accrue_interest(&mut state, clock.slot, market.referral_fee_bps);
+
+    state
+}
+
+/// Replaces the state after the anchor discriminator, the account size stays the same.
+async fn write_klend_account(context: &mut ProgramTestContext, address: &Pubkey, state: Vec<u8>) {
+    let mut account = get_account(&mut context.banks_client, *address)
+        .await
+        .unwrap();
+    assert_eq!(account.data.len(), 8 + state.len());
+    account.data.truncate(8);
+    account.data.extend(state);
+
+    context.set_account(address, &AccountSharedData::from(account));
+}
+
+pub async fn read_lending_market(
+    context: &mut ProgramTestContext,
+    market: &Pubkey,
+) -> LendingMarket {
+    let account = get_account(&mut context.banks_client, *market)
+        .await
+        .unwrap();
+
+    LendingMarket::try_from_slice(&account.data[8..]).unwrap()
+}
+
+/// Reserves in the order refresh_obligation expects them: deposits first, then borrows.
+pub fn obligation_reserves(obligation: &Obligation) -> Vec<Pubkey> {
+    let deposits = obligation
+        .deposits
+        .iter()
+        .map(|d| d.deposit_reserve)
+        .filter(|r| *r != Pubkey::default());
+    let borrows = obligation
+        .borrows
+        .iter()
+        .map(|b| b.borrow_reserve)
+        .filter(|r| *r != Pubkey::default());
+
+    deposits.chain(borrows).collect()
+}
+
+/// Inside an elevation group klend tracks the debt taken against each collateral, so borrow and
+/// repay take the obligation's deposit reserves as remaining accounts.
+pub fn extend_elevation_group_accounts(ix: &mut Instruction, obligation: &Obligation) {
+    if obligation.elevation_group == 0 {
+        return;
+    }
+    ix.accounts.extend(
+        obligation
+            .deposits
+            .iter()
+            .map(|d| d.deposit_reserve)
+            .filter(|r| *r != Pubkey::default())
+            .map(|r| AccountMeta::new(r, false)),
+    );
+}
+
+pub fn is_native(reserve: &ReserveInfo) -> bool {
+    reserve.liquidity_mint == spl_token::native_mint::id()
+}
+
+/// klend needs the obligation farm refreshed around the combined deposit and withdraw of a reserve
+/// with a collateral farm, the kamino_lending.so fixture has no handler for that refresh.
+fn assert_no_farm_collateral(reserve: &ReserveInfo) {
+    assert!(
+        !reserve.has_farm_collateral(),
+        "reserve {} has a collateral farm, obligation farm refresh is not supported",
+        reserve.symbol
+    );
+}
+
+fn compose_close_account_ix(user: &Keypair, account: &Pubkey) -> Instruction {
+    spl_token::instruction::close_account(
+        &spl_token::id(),
+        account,
+        &user.pubkey(),
+        &user.pubkey(),
+        &[&user.pubkey()],
+    )
+    .unwrap()
+}
@@ -0,0 +1,856 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::helper::{
+    create_token_account, get_or_create_associated_token_address, process_instructions_with_budget,
+    ComputeBudget,
+};
+use crate::kamino::{
+    compose_klend_borrow_obligation_liquidity_ix, compose_klend_deposit_obligation_collateral_ix,
+    compose_klend_deposit_reserve_liquidity_ix, compose_klend_flash_borrow_ix,
+    compose_klend_flash_repay_ix, compose_klend_redeem_reserve_collateral_ix,
+    compose_klend_repay_obligation_liquidity_ix, compose_klend_withdraw_obligation_collateral_ix,
+    compose_mock_swap_ix, Asset,
+};
+use crate::klend_math::{
+    amount_from_value, collateral_to_liquidity, liquidity_to_collateral, market_value,
+    obligation_debt, total_supply,
+};
+use crate::obligation_health::{borrow_factor, max_ltv, obligation_elevation_group};
+use crate::reserve_registry::ReserveInfo;
+use fixed::types::U68F60 as Fraction;
+use klend::{ElevationGroup, Obligation, Reserve};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::ProgramTestContext;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signer;
+
+use super::{
+    is_native, obligation_reserves, read_lending_market, read_obligation, read_projected_reserve,
+    LendingMarketClient,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum LeverageTarget {
+    Leverage(Fraction), // collateral value / net value, e.g. 3x
+    Ltv(Fraction),      // debt value / collateral value
+}
+
+impl LeverageTarget {
+    /// Panics for an LTV of 100% or more, `LeverageStrategy::plan` rejects those first.
+    pub fn leverage(&self) -> Fraction {
+        match self {
+            LeverageTarget::Leverage(leverage) => *leverage,
+            LeverageTarget::Ltv(ltv) => Fraction::ONE / (Fraction::ONE - *ltv),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LeverageError {
+    InvalidTarget,
+    ExceedsLoanToValue { ltv: Fraction, max_ltv: Fraction },
+    ExceedsBorrowLimit { borrowed: Fraction, limit: u64 },
+    ExceedsDepositLimit { supply: Fraction, limit: u64 },
+    InsufficientLiquidity { available: u64 },
+    InsufficientCollateral { deposited: u64 },
+}
+
+#[derive(Debug)]
+pub struct LeveragePlan {
+    /// Debt liquidity, flash borrowed then borrowed against the collateral
+    pub borrow_amount: u64,
+    /// Collateral liquidity bought with the borrowed debt
+    pub swap_output: u64,
+    /// Initial deposit + swap output
+    pub deposit_amount: u64,
+    /// Borrow factor adjusted debt value / collateral value
+    pub ltv: Fraction,
+}
+
+/// Opens a leveraged position for any collateral / debt pair of a market in one transaction:
+/// flash borrow debt -> swap to collateral -> deposit -> borrow debt -> flash repay.
+pub struct LeverageStrategy {
+    pub collateral: Asset,
+    pub debt: Asset,
+    pub initial_deposit: u64, // Collateral liquidity of the user
+    pub target: LeverageTarget,
+}
+
+impl LeverageStrategy {
+    /// Sizes the position at the reserve market prices and checks it against the collateral
+    /// loan-to-value and the reserve limits. Other positions of the obligation are not considered.
+    /// `group` is the elevation group of the obligation, its LTV and borrow factor apply.
+    pub fn plan(
+        &self,
+        collateral: &Reserve,
+        debt: &Reserve,
+        group: Option<&ElevationGroup>,
+    ) -> Result<LeveragePlan, LeverageError> {
+        if self.initial_deposit == 0 {
+            return Err(LeverageError::InvalidTarget);
+        }
+        if let LeverageTarget::Ltv(ltv) = self.target {
+            if ltv >= Fraction::ONE {
+                return Err(LeverageError::InvalidTarget);
+            }
+        }
+        let leverage = self.target.leverage();
+        if leverage < Fraction::ONE {
+            return Err(LeverageError::InvalidTarget);
+        }
+
+        let net_value = market_value(collateral, self.initial_deposit);
+        let borrow_amount = amount_from_value(debt, net_value * (leverage - Fraction::ONE));
+        let swap_output = amount_from_value(collateral, market_value(debt, borrow_amount));
+        let deposit_amount = self.initial_deposit + swap_output;
+
+        let borrow_fee = Fraction::from_bits(debt.config.fees.borrow_fee_sf as u128);
+        let debt_value = market_value(debt, borrow_amount)
+            * (Fraction::ONE + borrow_fee)
+            * borrow_factor(debt, group);
+        let ltv = debt_value / market_value(collateral, deposit_amount);
+        check_limits(collateral, debt, group, borrow_amount, deposit_amount, ltv)?;
+
+        Ok(LeveragePlan {
+            borrow_amount,
+            swap_output,
+            deposit_amount,
+            ltv,
+        })
+    }
+
+    /// `user` holds the initial deposit and must be the mint authority of the collateral (admin).
+    pub async fn execute(
+        &self,
+        client: &LendingMarketClient,
+        user: &Keypair,
+        obligation: &Pubkey,
+    ) -> LeveragePlan {
+        let collateral = client.reserve(self.collateral);
+        let debt = client.reserve(self.debt);
+
+        // Fresh market prices for sizing the position
+        client.refresh_reserve(user, self.collateral).await;
+        client.refresh_reserve(user, self.debt).await;
+
+        let context: &mut ProgramTestContext = &mut client.context.borrow_mut();
+        let collateral_state = read_projected_reserve(context, &collateral.address).await;
+        let debt_state = read_projected_reserve(context, &debt.address).await;
+        let obligation_state = read_obligation(context, obligation).await;
+        let market = read_lending_market(context, &client.market).await;
+
+        let plan = self
+            .plan(
+                &collateral_state,
+                &debt_state,
+                obligation_elevation_group(&obligation_state, &market),
+            )
+            .unwrap_or_else(|e| panic!("invalid leverage strategy: {:?}", e));
+
+        increase_position(
+            client,
+            context,
+            user,
+            obligation,
+            collateral,
+            &collateral_state,
+            debt,
+            &debt_state,
+            &plan,
+        )
+        .await;
+
+        plan
+    }
+}
+
+/// Checks an increase of the position against the collateral loan-to-value, the elevation group
+/// one inside of a group, and the reserve limits.
+fn check_limits(
+    collateral: &Reserve,
+    debt: &Reserve,
+    group: Option<&ElevationGroup>,
+    borrow_amount: u64,
+    deposit_amount: u64,
+    ltv: Fraction,
+) -> Result<(), LeverageError> {
+    let max_ltv = max_ltv(collateral, group);
+    if ltv > max_ltv {
+        return Err(LeverageError::ExceedsLoanToValue { ltv, max_ltv });
+    }
+
+    let borrowed =
+        Fraction::from_bits(debt.liquidity.borrowed_amount_sf) + Fraction::from(borrow_amount);
+    if borrowed > Fraction::from(debt.config.borrow_limit) {
+        return Err(LeverageError::ExceedsBorrowLimit {
+            borrowed,
+            limit: debt.config.borrow_limit,
+        });
+    }
+
+    if debt.liquidity.available_amount < borrow_amount {
+        return Err(LeverageError::InsufficientLiquidity {
+            available: debt.liquidity.available_amount,
+        });
+    }
+
+    let supply = total_supply(collateral) + Fraction::from(deposit_amount);
+    if supply > Fraction::from(collateral.config.deposit_limit) {
+        return Err(LeverageError::ExceedsDepositLimit {
+            supply,
+            limit: collateral.config.deposit_limit,
+        });
+    }
+
+    Ok(())
+}
+
+/// flash borrow debt -> swap to collateral -> deposit -> borrow debt -> flash repay
+async fn increase_position(
+    client: &LendingMarketClient,
+    context: &mut ProgramTestContext,
+    user: &Keypair,
+    obligation: &Pubkey,
+    collateral: &ReserveInfo,
+    collateral_state: &Reserve,
+    debt: &ReserveInfo,
+    debt_state: &Reserve,
+    plan: &LeveragePlan,
+) {
+    assert!(!is_native(collateral), "wsol collateral is not supported");
+    assert_eq!(
+        debt_state.config.fees.flash_loan_fee_sf, 0,
+        "flash loan fees are not funded"
+    );
+
+    let collateral_amount = liquidity_to_collateral(collateral_state, plan.deposit_amount);
+
+    let user_debt_account = Keypair::new();
+    let swap_sink = Keypair::new();
+    for account in [&user_debt_account, &swap_sink] {
+        create_token_account(
+            context,
+            user,
+            account,
+            &debt.liquidity_mint,
+            &user.pubkey(),
+            0,
+        )
+        .await
+        .unwrap();
+    }
+
+    let user_collateral_liquidity = get_or_create_associated_token_address(
+        context,
+        user,
+        &user.pubkey(),
+        &collateral.liquidity_mint,
+    )
+    .await;
+    let user_collateral = get_or_create_associated_token_address(
+        context,
+        user,
+        &user.pubkey(),
+        &collateral.collateral_mint,
+    )
+    .await;
+
+    let obligation_state = read_obligation(context, obligation).await;
+    let budget = ComputeBudget::default();
+    let mut instructions: Vec<Instruction> = vec![];
+
+    // 1. Flash borrow debt, must be the first instruction (see flash repay)
+    instructions.push(compose_klend_flash_borrow_ix(
+        &user.pubkey(),
+        &client.market,
+        &client.market_authority,
+        &debt.address,
+        &debt.liquidity_mint,
+        &debt.liquidity_supply_vault,
+        &user_debt_account.pubkey(),
+        &debt.liquidity_fee_vault,
+        plan.borrow_amount,
+    ));
+
+    // 2. Swap debt to collateral
+    instructions.extend_from_slice(&compose_mock_swap_ix(
+        &user.pubkey(),
+        &user_debt_account.pubkey(),
+        &swap_sink.pubkey(),
+        plan.borrow_amount,
+        &collateral.liquidity_mint,
+        &user_collateral_liquidity,
+        None,
+        plan.swap_output,
+    ));
+
+    // 3. Deposit collateral to reserve
+    instructions.push(compose_klend_deposit_reserve_liquidity_ix(
+        &user.pubkey(),
+        &collateral.address,
+        &client.market,
+        &client.market_authority,
+        &collateral.liquidity_mint,
+        &collateral.liquidity_supply_vault,
+        &collateral.collateral_mint,
+        &user_collateral_liquidity,
+        &user_collateral,
+        plan.deposit_amount,
+    ));
+
+    // 4. Deposit obligation collateral
+    instructions.extend(client.compose_refresh_obligation_ixs(
+        obligation,
+        &obligation_reserves(&obligation_state),
+        Some(&collateral.address),
+    ));
+
+    instructions.push(compose_klend_deposit_obligation_collateral_ix(
+        &user.pubkey(),
+        obligation,
+        &client.market,
+        &collateral.address,
+        &collateral.collateral_supply_vault,
+        &user_collateral,
+        collateral_amount,
+    ));
+
+    // 5. Borrow obligation liquidity, the obligation now holds the collateral deposit
+    instructions.extend(client.compose_refresh_obligation_ixs(
+        obligation,
+        &reserves_after_deposit(&obligation_state, &collateral.address),
+        Some(&debt.address),
+    ));
+
+    instructions.push(compose_klend_borrow_obligation_liquidity_ix(
+        &user.pubkey(),
+        obligation,
+        &client.market,
+        &client.market_authority,
+        &debt.address,
+        &debt.liquidity_mint,
+        &debt.liquidity_supply_vault,
+        &debt.liquidity_fee_vault,
+        &user_debt_account.pubkey(),
+        plan.borrow_amount,
+    ));
+
+    // 6. Flash repay debt
+    instructions.push(compose_klend_flash_repay_ix(
+        &user.pubkey(),
+        &client.market,
+        &client.market_authority,
+        &debt.address,
+        &debt.liquidity_mint,
+        &debt.liquidity_supply_vault,
+        &user_debt_account.pubkey(), // Must be the same one of borrow ix
+        &debt.liquidity_fee_vault,
+        plan.borrow_amount,
+        budget.instruction_count() as u8,
+    ));
+
+    process_instructions_with_budget(context, user, &instructions, budget).await;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum DeleverageTarget {
+    Repay(u64),    // Debt liquidity to repay, capped at the obligation debt
+    Ltv(Fraction), // Borrow factor adjusted debt value / deposited value
+}
+
+#[derive(Debug)]
+pub struct DeleveragePlan {
+    /// Debt liquidity, flash borrowed then repaid to the obligation
+    pub repay_amount: u64,
+    /// Collateral tokens withdrawn from the obligation and redeemed
+    pub withdraw_amount: u64,
+    /// Redeemed collateral liquidity sold for the flash loan
+    pub swap_input: u64,
+    /// Obligation LTV once deleveraged
+    pub ltv: Fraction,
+}
+
+/// Reduces a leveraged position without closing it, in one transaction:
+/// flash borrow debt -> repay -> withdraw collateral -> redeem -> swap to debt -> flash repay.
+pub struct Deleverage {
+    pub collateral: Asset,
+    pub debt: Asset,
+    pub target: DeleverageTarget,
+}
+
+impl Deleverage {
+    /// `obligation` must be freshly refreshed, its values are used as is. `group` is its
+    /// elevation group.
+    pub fn plan(
+        &self,
+        collateral: &Reserve,
+        debt: &Reserve,
+        collateral_reserve: &Pubkey,
+        debt_reserve: &Pubkey,
+        obligation: &Obligation,
+        group: Option<&ElevationGroup>,
+    ) -> Result<DeleveragePlan, LeverageError> {
+        let deposited_amount = obligation
+            .deposits
+            .iter()
+            .find(|d| d.deposit_reserve == *collateral_reserve)
+            .ok_or(LeverageError::InvalidTarget)?
+            .deposited_amount;
+        let debt_amount: u64 = obligation_debt(
+            obligation
+                .borrows
+                .iter()
+                .find(|b| b.borrow_reserve == *debt_reserve)
+                .ok_or(LeverageError::InvalidTarget)?,
+            debt,
+        )
+        .ceil()
+        .to_num();
+
+        let deposited_value = Fraction::from_bits(obligation.deposited_value_sf);
+        let debt_value = Fraction::from_bits(obligation.borrow_factor_adjusted_debt_value_sf);
+        let borrow_factor = borrow_factor(debt, group);
+        if deposited_value == Fraction::ZERO {
+            return Err(LeverageError::InvalidTarget);
+        }
+
+        let repay_amount = match self.target {
+            DeleverageTarget::Repay(amount) => amount,
+            DeleverageTarget::Ltv(ltv) => {
+                if ltv >= debt_value / deposited_value || ltv >= borrow_factor {
+                    return Err(LeverageError::InvalidTarget);
+                }
+                // (debt_value - x * borrow_factor) / (deposited_value - x) = ltv
+                let repay_value = (debt_value - ltv * deposited_value) / (borrow_factor - ltv);
+                amount_from_value(debt, repay_value)
+            }
+        }
+        .min(debt_amount);
+
+        // Sell a bit more than the debt is worth, the mock swap rounds down
+        let swap_input = amount_from_value(collateral, market_value(debt, repay_amount)) + 1;
+        let mut withdraw_amount = liquidity_to_collateral(collateral, swap_input);
+        while collateral_to_liquidity(collateral, withdraw_amount) < swap_input {
+            withdraw_amount += 1;
+        }
+        if withdraw_amount > deposited_amount {
+            return Err(LeverageError::InsufficientCollateral {
+                deposited: deposited_amount,
+            });
+        }
+
+        let withdraw_value = market_value(
+            collateral,
+            collateral_to_liquidity(collateral, withdraw_amount),
+        );
+        let remaining_value = deposited_value
+            .checked_sub(withdraw_value)
+            .filter(|value| *value > Fraction::ZERO)
+            .ok_or(LeverageError::InsufficientCollateral {
+                deposited: deposited_amount,
+            })?;
+        // A full repay can be worth more than the recorded debt value
+        let ltv = debt_value
+            .checked_sub(market_value(debt, repay_amount) * borrow_factor)
+            .unwrap_or(Fraction::ZERO)
+            / remaining_value;
+
+        Ok(DeleveragePlan {
+            repay_amount,
+            withdraw_amount,
+            swap_input,
+            ltv,
+        })
+    }
+
+    /// For a non-native debt `user` must be the mint authority of the debt (admin).
+    pub async fn execute(
+        &self,
+        client: &LendingMarketClient,
+        user: &Keypair,
+        obligation: &Pubkey,
+    ) -> DeleveragePlan {
+        let collateral = client.reserve(self.collateral);
+        let debt = client.reserve(self.debt);
+
+        client.refresh_obligation(user, obligation).await;
+
+        let context: &mut ProgramTestContext = &mut client.context.borrow_mut();
+        let collateral_state = read_projected_reserve(context, &collateral.address).await;
+        let debt_state = read_projected_reserve(context, &debt.address).await;
+        let obligation_state = read_obligation(context, obligation).await;
+        let market = read_lending_market(context, &client.market).await;
+
+        let plan = self
+            .plan(
+                &collateral_state,
+                &debt_state,
+                &collateral.address,
+                &debt.address,
+                &obligation_state,
+                obligation_elevation_group(&obligation_state, &market),
+            )
+            .unwrap_or_else(|e| panic!("invalid deleverage: {:?}", e));
+
+        decrease_position(
+            client,
+            context,
+            user,
+            obligation,
+            collateral,
+            debt,
+            &debt_state,
+            &plan,
+        )
+        .await;
+
+        plan
+    }
+}
+
+#[derive(Debug)]
+pub enum RebalancePlan {
+    Leverage(LeveragePlan),
+    Deleverage(DeleveragePlan),
+    /// Already at the target, nothing to send
+    Unchanged,
+}
+
+/// Moves an open position to `target_ltv`, levering up or deleveraging as needed.
+pub struct Rebalance {
+    pub collateral: Asset,
+    pub debt: Asset,
+    pub target_ltv: Fraction, // Borrow factor adjusted debt value / deposited value
+}
+
+impl Rebalance {
+    /// `obligation` must be freshly refreshed, its values are used as is. `group` is its
+    /// elevation group.
+    pub fn plan(
+        &self,
+        collateral: &Reserve,
+        debt: &Reserve,
+        collateral_reserve: &Pubkey,
+        debt_reserve: &Pubkey,
+        obligation: &Obligation,
+        group: Option<&ElevationGroup>,
+    ) -> Result<RebalancePlan, LeverageError> {
+        let deposited_value = Fraction::from_bits(obligation.deposited_value_sf);
+        let debt_value = Fraction::from_bits(obligation.borrow_factor_adjusted_debt_value_sf);
+        if deposited_value == Fraction::ZERO {
+            return Err(LeverageError::InvalidTarget);
+        }
+
+        if debt_value / deposited_value > self.target_ltv {
+            let deleverage = Deleverage {
+                collateral: self.collateral,
+                debt: self.debt,
+                target: DeleverageTarget::Ltv(self.target_ltv),
+            };
+            return deleverage
+                .plan(
+                    collateral,
+                    debt,
+                    collateral_reserve,
+                    debt_reserve,
+                    obligation,
+                    group,
+                )
+                .map(|plan| match plan.repay_amount {
+                    0 => RebalancePlan::Unchanged,
+                    _ => RebalancePlan::Deleverage(plan),
+                });
+        }
+
+        let borrow_fee = Fraction::from_bits(debt.config.fees.borrow_fee_sf as u128);
+        let adjusted_borrow_factor = (Fraction::ONE + borrow_fee) * borrow_factor(debt, group);
+        if self.target_ltv >= adjusted_borrow_factor {
+            return Err(LeverageError::InvalidTarget);
+        }
+
+        // (debt_value + x * adjusted_borrow_factor) / (deposited_value + x) = target_ltv
+        let borrow_value = (self.target_ltv * deposited_value)
+            .checked_sub(debt_value)
+            .unwrap_or(Fraction::ZERO)
+            / (adjusted_borrow_factor - self.target_ltv);
+        let borrow_amount = amount_from_value(debt, borrow_value);
+        if borrow_amount == 0 {
+            return Ok(RebalancePlan::Unchanged);
+        }
+        let swap_output = amount_from_value(collateral, market_value(debt, borrow_amount));
+        let ltv = (debt_value + market_value(debt, borrow_amount) * adjusted_borrow_factor)
+            / (deposited_value + market_value(collateral, swap_output));
+        check_limits(collateral, debt, group, borrow_amount, swap_output, ltv)?;
+
+        Ok(RebalancePlan::Leverage(LeveragePlan {
+            borrow_amount,
+            swap_output,
+            deposit_amount: swap_output,
+            ltv,
+        }))
+    }
+
+    /// `user` must be the mint authority of the collateral, and of the debt if not native (admin).
+    pub async fn execute(
+        &self,
+        client: &LendingMarketClient,
+        user: &Keypair,
+        obligation: &Pubkey,
+    ) -> RebalancePlan {
+        let collateral = client.reserve(self.collateral);
+        let debt = client.reserve(self.debt);
+
+        client.refresh_obligation(user, obligation).await;
+
+        let context: &mut ProgramTestContext = &mut client.context.borrow_mut();
+        let collateral_state = read_projected_reserve(context, &collateral.address).await;
+        let debt_state = read_projected_reserve(context, &debt.address).await;
+        let obligation_state = read_obligation(context, obligation).await;
+        let market = read_lending_market(context, &client.market).await;
+
+        let plan = self
+            .plan(
+                &collateral_state,
+                &debt_state,
+                &collateral.address,
+                &debt.address,
+                &obligation_state,
+                obligation_elevation_group(&obligation_state, &market),
+            )
+            .unwrap_or_else(|e| panic!("invalid rebalance: {:?}", e));
+
+        match &plan {
+            RebalancePlan::Leverage(plan) => {
+                increase_position(
+                    client,
+                    context,
+                    user,
+                    obligation,
+                    collateral,
+                    &collateral_state,
+                    debt,
+                    &debt_state,
+                    plan,
+                )
+                .await
+            }
+            RebalancePlan::Deleverage(plan) => {
+                decrease_position(
+                    client,
+                    context,
+                    user,
+                    obligation,
+                    collateral,
+                    debt,
+                    &debt_state,
+                    plan,
+                )
+                .await
+            }
+            RebalancePlan::Unchanged => {}
+        }
+
+        plan
+    }
+}
+
+/// flash borrow debt -> repay -> withdraw collateral -> redeem -> swap to debt -> flash repay
+async fn decrease_position(
+    client: &LendingMarketClient,
+    context: &mut ProgramTestContext,
+    user: &Keypair,
+    obligation: &Pubkey,
+    collateral: &ReserveInfo,
+    debt: &ReserveInfo,
+    debt_state: &Reserve,
+    plan: &DeleveragePlan,
+) {
+    assert_eq!(
+        debt_state.config.fees.flash_loan_fee_sf, 0,
+        "flash loan fees are not funded"
+    );
+
+    let user_debt_account = Keypair::new();
+    create_token_account(
+        context,
+        user,
+        &user_debt_account,
+        &debt.liquidity_mint,
+        &user.pubkey(),
+        0,
+    )
+    .await
+    .unwrap();
+
+    // The mock swap pays wsol out of an account funded by the user
+    let swap_funding = Keypair::new();
+    if is_native(debt) {
+        create_token_account(
+            context,
+            user,
+            &swap_funding,
+            &debt.liquidity_mint,
+            &user.pubkey(),
+            plan.repay_amount,
+        )
+        .await
+        .unwrap();
+    }
+
+    let swap_sink = Keypair::new();
+    create_token_account(
+        context,
+        user,
+        &swap_sink,
+        &collateral.liquidity_mint,
+        &user.pubkey(),
+        0,
+    )
+    .await
+    .unwrap();
+
+    let user_collateral_liquidity = get_or_create_associated_token_address(
+        context,
+        user,
+        &user.pubkey(),
+        &collateral.liquidity_mint,
+    )
+    .await;
+    let user_collateral = get_or_create_associated_token_address(
+        context,
+        user,
+        &user.pubkey(),
+        &collateral.collateral_mint,
+    )
+    .await;
+
+    let obligation_state = read_obligation(context, obligation).await;
+    let budget = ComputeBudget::default();
+    let mut instructions: Vec<Instruction> = vec![];
+
+    // 1. Flash borrow debt, must be the first instruction (see flash repay)
+    instructions.push(compose_klend_flash_borrow_ix(
+        &user.pubkey(),
+        &client.market,
+        &client.market_authority,
+        &debt.address,
+        &debt.liquidity_mint,
+        &debt.liquidity_supply_vault,
+        &user_debt_account.pubkey(),
+        &debt.liquidity_fee_vault,
+        plan.repay_amount,
+    ));
+
+    // 2. Repay obligation liquidity
+    let reserves = obligation_reserves(&obligation_state);
+    instructions.extend(client.compose_refresh_obligation_ixs(
+        obligation,
+        &reserves,
+        Some(&debt.address),
+    ));
+
+    instructions.push(compose_klend_repay_obligation_liquidity_ix(
+        &user.pubkey(),
+        obligation,
+        &client.market,
+        &debt.address,
+        &debt.liquidity_mint,
+        &debt.liquidity_supply_vault,
+        &user_debt_account.pubkey(),
+        plan.repay_amount,
+    ));
+
+    // 3. Withdraw obligation collateral, a fully repaid borrow is removed from the obligation
+    let debt_amount: u64 = obligation_state
+        .borrows
+        .iter()
+        .find(|b| b.borrow_reserve == debt.address)
+        .map(|b| obligation_debt(b, debt_state).ceil().to_num())
+        .unwrap();
+    let debt_repaid = plan.repay_amount >= debt_amount;
+    let reserves_after_repay: Vec<Pubkey> = reserves
+        .iter()
+        .filter(|r| !(debt_repaid && **r == debt.address))
+        .copied()
+        .collect();
+    instructions.extend(client.compose_refresh_obligation_ixs(
+        obligation,
+        &reserves_after_repay,
+        Some(&collateral.address),
+    ));
+
+    instructions.push(compose_klend_withdraw_obligation_collateral_ix(
+        &user.pubkey(),
+        obligation,
+        &client.market,
+        &client.market_authority,
+        &collateral.address,
+        &collateral.collateral_supply_vault,
+        &user_collateral,
+        plan.withdraw_amount,
+    ));
+
+    // 4. Redeem collateral liquidity
+    instructions.push(compose_klend_redeem_reserve_collateral_ix(
+        &user.pubkey(),
+        &collateral.address,
+        &client.market,
+        &client.market_authority,
+        &collateral.liquidity_mint,
+        &collateral.liquidity_supply_vault,
+        &collateral.collateral_mint,
+        &user_collateral,
+        &user_collateral_liquidity,
+        plan.withdraw_amount,
+    ));
+
+    // 5. Swap collateral to debt
+    instructions.extend_from_slice(&compose_mock_swap_ix(
+        &user.pubkey(),
+        &user_collateral_liquidity,
+        &swap_sink.pubkey(),
+        plan.swap_input,
+        &debt.liquidity_mint,
+        &user_debt_account.pubkey(),
+        Some(&swap_funding.pubkey()),
+        plan.repay_amount,
+    ));
+
+    // 6. Flash repay debt
+    instructions.push(compose_klend_flash_repay_ix(
+        &user.pubkey(),
+        &client.market,
+        &client.market_authority,
+        &debt.address,
+        &debt.liquidity_mint,
+        &debt.liquidity_supply_vault,
+        &user_debt_account.pubkey(), // Must be the same one of borrow ix
+        &debt.liquidity_fee_vault,
+        plan.repay_amount,
+        budget.instruction_count() as u8,
+    ));
+
+    process_instructions_with_budget(context, user, &instructions, budget).await;
+}
+
+/// Obligation reserves once `deposit_reserve` is part of its deposits.
+fn reserves_after_deposit(obligation: &Obligation, deposit_reserve: &Pubkey) -> Vec<Pubkey> {
+    let mut deposits: Vec<Pubkey> = obligation
+        .deposits
+        .iter()
+        .map(|d| d.deposit_reserve)
+        .filter(|r| *r != Pubkey::default())
+        .collect();
+    if !deposits.contains(deposit_reserve) {
+        deposits.push(*deposit_reserve);
+    }
+
+    let borrows = obligation
+        .borrows
+        .iter()
+        .map(|b| b.borrow_reserve)
+        .filter(|r| *r != Pubkey::default());
+
+    deposits.into_iter().chain(borrows).collect()
+}
@@ -0,0 +1,367 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::helper::{
+    create_token_account, get_or_create_associated_token_address, process_instructions_with_budget,
+    ComputeBudget,
+};
+use crate::kamino::{
+    compose_klend_flash_borrow_ix, compose_klend_flash_repay_ix,
+    compose_klend_liquidate_obligation_and_redeem_reserve_collateral_ix, compose_mock_swap_ix,
+    Asset,
+};
+use crate::klend_math::{amount_from_value, fraction_from_bps, fraction_from_percent};
+use crate::obligation_health::{
+    borrow_factor, liquidation_threshold, obligation_elevation_group, ObligationHealth,
+};
+use fixed::types::U68F60 as Fraction;
+use klend::{LendingMarket, Obligation, Reserve};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::ProgramTestContext;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signer;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::{
+    is_native, obligation_reserves, read_lending_market, read_obligation, read_projected_reserve,
+    LendingMarketClient, LiquidationOutcome, RateXTestContext, UserTestContext,
+};
+
+/// Best liquidation of an unhealthy obligation, as estimated off chain.
+#[derive(Debug)]
+pub struct LiquidationCandidate {
+    pub obligation: Pubkey,
+    pub market: Pubkey,
+    pub ltv: Fraction,
+    pub liquidation_ltv: Fraction,
+    pub repay_reserve: Pubkey,
+    pub withdraw_reserve: Pubkey,
+    /// Debt liquidity to repay, within the close factor and the collateral left
+    pub repay_amount: u64,
+    pub repay_value: Fraction,
+    pub bonus: Fraction,
+    /// Bonus value left to the liquidator once the protocol took its fee
+    pub expected_profit: Fraction,
+}
+
+#[derive(Debug)]
+pub struct LiquidationReport {
+    pub candidate: LiquidationCandidate,
+    /// With flash loans, received amounts are net of the collateral swapped back to repay the loan
+    pub outcome: LiquidationOutcome,
+}
+
+/// Liquidates every unhealthy obligation of the session, most profitable first.
+pub struct Liquidator {
+    /// Borrow the repaid liquidity with a flash loan and swap part of the collateral back to repay
+    /// it, instead of paying from the liquidator wallet
+    pub flash_loan: bool,
+    pub max_liquidations: usize,
+}
+
+impl Liquidator {
+    /// Liquidation candidates of all tracked obligations, sorted by expected profit.
+    pub async fn scan(&self, rtc: &RateXTestContext) -> Vec<LiquidationCandidate> {
+        let mut candidates = vec![];
+        for obligation in rtc.obligations().await {
+            let context: &mut ProgramTestContext = &mut rtc.context.borrow_mut();
+            let state = read_obligation(context, &obligation).await;
+            let market = rtc.lending_market_client(&state.lending_market);
+
+            let mut reserves = HashMap::new();
+            for reserve in market.reserves() {
+                reserves.insert(reserve, read_projected_reserve(context, &reserve).await);
+            }
+            let market_state = read_lending_market(context, &state.lending_market).await;
+
+            if let Some(candidate) = best_candidate(&obligation, &state, &reserves, &market_state) {
+                candidates.push(candidate);
+            }
+        }
+
+        candidates.sort_by_key(|c| Reverse(c.expected_profit));
+        candidates
+    }
+
+    /// Refreshes the market, then liquidates the best candidate until none is left.
+    pub async fn run(
+        &self,
+        rtc: &RateXTestContext,
+        liquidator: &UserTestContext,
+    ) -> Vec<LiquidationReport> {
+        let mut reports = vec![];
+        while reports.len() < self.max_liquidations {
+            rtc.refresh_all(&rtc.obligations().await).await;
+
+            let candidate = match self.scan(rtc).await.into_iter().next() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+
+            let client = rtc.lending_market_client(&candidate.market);
+            let repay = Asset::Reserve(candidate.repay_reserve);
+            let withdraw = Asset::Reserve(candidate.withdraw_reserve);
+            let outcome = if self.flash_loan {
+                // Boxed, the nested futures overflow the test thread stack otherwise
+                let liquidation = Box::pin(flash_liquidate(&client, &liquidator.user, &candidate));
+                liquidator
+                    .measure_liquidation(
+                        &client,
+                        &candidate.obligation,
+                        repay,
+                        withdraw,
+                        liquidation,
+                    )
+                    .await
+            } else {
+                liquidator
+                    .klend_liquidate(
+                        &client,
+                        &candidate.obligation,
+                        repay,
+                        withdraw,
+                        candidate.repay_amount,
+                    )
+                    .await
+            };
+
+            reports.push(LiquidationReport { candidate, outcome });
+        }
+
+        reports
+    }
+}
+
+/// klend makes liquidators repay the debt with the highest borrow factor and withdraw the
+/// collateral with the lowest liquidation threshold first. Among those pairs, picks the one
+/// with the largest bonus. Obligations with a reserve outside of `reserves` are skipped.
+fn best_candidate(
+    obligation: &Pubkey,
+    state: &Obligation,
+    reserves: &HashMap<Pubkey, Reserve>,
+    market: &LendingMarket,
+) -> Option<LiquidationCandidate> {
+    let health = ObligationHealth::try_new(state, market, reserves).ok()?;
+    if !health.is_liquidatable() || health.deposited_value == Fraction::ZERO {
+        return None;
+    }
+    let group = obligation_elevation_group(state, market);
+
+    let highest_borrow_factor = health
+        .borrows
+        .iter()
+        .map(|(reserve, _)| borrow_factor(&reserves[reserve], group))
+        .max()?;
+    let lowest_liquidation_threshold = health
+        .deposits
+        .iter()
+        .map(|(reserve, _)| liquidation_threshold(&reserves[reserve], group))
+        .min()?;
+
+    // Small obligations are closed in full, others by close factor slices
+    let close_factor =
+        if health.borrowed_value < Fraction::from(market.min_full_liquidation_value_threshold) {
+            Fraction::ONE
+        } else {
+            fraction_from_percent(market.liquidation_max_debt_close_factor_pct as u64)
+        };
+    let max_value_at_once = Fraction::from(market.max_liquidatable_debt_market_value_at_once);
+
+    let mut best: Option<LiquidationCandidate> = None;
+    for (repay_reserve, debt_value) in health
+        .borrows
+        .iter()
+        .filter(|(r, _)| borrow_factor(&reserves[r], group) == highest_borrow_factor)
+    {
+        for (withdraw_reserve, collateral_value) in health.deposits.iter().filter(|(r, _)| {
+            liquidation_threshold(&reserves[r], group) == lowest_liquidation_threshold
+        }) {
+            let config = &reserves[withdraw_reserve].config;
+            // Capped by the group bonus inside of an elevation group
+            let max_bonus_bps = group.map_or(config.max_liquidation_bonus_bps, |group| {
+                config
+                    .max_liquidation_bonus_bps
+                    .min(group.max_liquidation_bonus_bps)
+            });
+            let bonus = (health.ltv - health.liquidation_ltv)
+                .max(fraction_from_bps(config.min_liquidation_bonus_bps as u64))
+                .min(fraction_from_bps(max_bonus_bps as u64));
+
+            let repay_value = (*debt_value * close_factor)
+                .min(max_value_at_once)
+                .min(*collateral_value / (Fraction::ONE + bonus));
+            let protocol_fee = fraction_from_percent(config.protocol_liquidation_fee_pct as u64);
+            let expected_profit = repay_value * bonus * (Fraction::ONE - protocol_fee);
+
+            let better = match &best {
+                Some(b) => expected_profit > b.expected_profit,
+                None => true,
+            };
+            if better {
+                best = Some(LiquidationCandidate {
+                    obligation: *obligation,
+                    market: state.lending_market,
+                    ltv: health.ltv,
+                    liquidation_ltv: health.liquidation_ltv,
+                    repay_reserve: *repay_reserve,
+                    withdraw_reserve: *withdraw_reserve,
+                    repay_amount: amount_from_value(&reserves[repay_reserve], repay_value),
+                    repay_value,
+                    bonus,
+                    expected_profit,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+/// Flash borrows the repaid liquidity, liquidates, then swaps the equivalent collateral back to
+/// repay the loan, the liquidator keeps the rest of the redeemed collateral. The mock swap mints
+/// the debt token, so a non wsol debt needs the liquidator to be its mint authority.
+async fn flash_liquidate(
+    client: &LendingMarketClient,
+    liquidator: &Keypair,
+    candidate: &LiquidationCandidate,
+) {
+    let context: &mut ProgramTestContext = &mut client.context.borrow_mut();
+    let repay = client.reserve(Asset::Reserve(candidate.repay_reserve));
+    let withdraw = client.reserve(Asset::Reserve(candidate.withdraw_reserve));
+    assert!(!is_native(withdraw), "wsol collateral is not supported");
+
+    let repay_state = read_projected_reserve(context, &repay.address).await;
+    assert_eq!(
+        repay_state.config.fees.flash_loan_fee_sf, 0,
+        "flash loan fees are not funded"
+    );
+    let withdraw_state = read_projected_reserve(context, &withdraw.address).await;
+    let swap_input = amount_from_value(&withdraw_state, candidate.repay_value);
+
+    let user_repay_account = Keypair::new();
+    create_token_account(
+        context,
+        liquidator,
+        &user_repay_account,
+        &repay.liquidity_mint,
+        &liquidator.pubkey(),
+        0,
+    )
+    .await
+    .unwrap();
+
+    // The mock swap pays wsol out of an account funded by the liquidator
+    let swap_funding = Keypair::new();
+    if is_native(repay) {
+        create_token_account(
+            context,
+            liquidator,
+            &swap_funding,
+            &repay.liquidity_mint,
+            &liquidator.pubkey(),
+            candidate.repay_amount,
+        )
+        .await
+        .unwrap();
+    }
+
+    let swap_sink = Keypair::new();
+    create_token_account(
+        context,
+        liquidator,
+        &swap_sink,
+        &withdraw.liquidity_mint,
+        &liquidator.pubkey(),
+        0,
+    )
+    .await
+    .unwrap();
+
+    let user_destination_collateral = get_or_create_associated_token_address(
+        context,
+        liquidator,
+        &liquidator.pubkey(),
+        &withdraw.collateral_mint,
+    )
+    .await;
+    let user_destination_liquidity = get_or_create_associated_token_address(
+        context,
+        liquidator,
+        &liquidator.pubkey(),
+        &withdraw.liquidity_mint,
+    )
+    .await;
+
+    // 1. Flash borrow the debt liquidity, must be the first instruction (see flash repay)
+    let budget = ComputeBudget::default();
+    let mut instructions = vec![compose_klend_flash_borrow_ix(
+        &liquidator.pubkey(),
+        &client.market,
+        &client.market_authority,
+        &repay.address,
+        &repay.liquidity_mint,
+        &repay.liquidity_supply_vault,
+        &user_repay_account.pubkey(),
+        &repay.liquidity_fee_vault,
+        candidate.repay_amount,
+    )];
+
+    // 2. Liquidate and redeem the collateral
+    let obligation_state = read_obligation(context, &candidate.obligation).await;
+    instructions.extend(client.compose_refresh_obligation_ixs(
+        &candidate.obligation,
+        &obligation_reserves(&obligation_state),
+        None,
+    ));
+    instructions.push(
+        compose_klend_liquidate_obligation_and_redeem_reserve_collateral_ix(
+            &liquidator.pubkey(),
+            &candidate.obligation,
+            &client.market,
+            &client.market_authority,
+            &repay.address,
+            &repay.liquidity_mint,
+            &repay.liquidity_supply_vault,
+            &withdraw.address,
+            &withdraw.liquidity_mint,
+            &withdraw.collateral_mint,
+            &withdraw.collateral_supply_vault,
+            &withdraw.liquidity_supply_vault,
+            &withdraw.liquidity_fee_vault,
+            &user_repay_account.pubkey(),
+            &user_destination_collateral,
+            &user_destination_liquidity,
+            candidate.repay_amount,
+            0,
+            0,
+        ),
+    );
+
+    // 3. Swap the repaid value of collateral back to debt
+    instructions.extend_from_slice(&compose_mock_swap_ix(
+        &liquidator.pubkey(),
+        &user_destination_liquidity,
+        &swap_sink.pubkey(),
+        swap_input,
+        &repay.liquidity_mint,
+        &user_repay_account.pubkey(),
+        Some(&swap_funding.pubkey()),
+        candidate.repay_amount,
+    ));
+
+    // 4. Flash repay the debt liquidity
+    instructions.push(compose_klend_flash_repay_ix(
+        &liquidator.pubkey(),
+        &client.market,
+        &client.market_authority,
+        &repay.address,
+        &repay.liquidity_mint,
+        &repay.liquidity_supply_vault,
+        &user_repay_account.pubkey(), // Must be the same one of borrow ix
+        &repay.liquidity_fee_vault,
+        candidate.repay_amount,
+        budget.instruction_count() as u8,
+    ));
+
+    process_instructions_with_budget(context, liquidator, &instructions, budget).await;
+}
@@ -0,0 +1,17 @@
+// The clients share one ProgramTestContext through Rc<RefCell<_>> and hold the borrow across
+// banks client calls, tests are single threaded so the borrow never overlaps.
+#![allow(clippy::await_holding_refcell_ref)]
+
+pub mod lending_market_client;
+pub mod leverage_strategy;
+pub mod liquidator;
+pub mod price_replay;
+pub mod ratex_test_context;
+pub mod user_test_context;
+
+pub use lending_market_client::*;
+pub use leverage_strategy::*;
+pub use liquidator::*;
+pub use price_replay::*;
+pub use ratex_test_context::*;
+pub use user_test_context::*;
@@ -0,0 +1,199 @@
+use crate::helper::read_account_data;
+use crate::kamino::Asset;
+use crate::reserve_registry::ReserveRegistry;
+use fixed::types::U68F60 as Fraction;
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+use std::fmt::Write;
+use std::time::Duration;
+
+use super::{read_obligation, RateXTestContext};
+
+#[derive(Clone, Copy, Debug)]
+pub enum PriceSource {
+    Asset(Asset),    // Reserve price in the quote currency, e.g. "JITOSOL"
+    ScopeEntry(u16), // Raw scope entry, e.g. "51" for the jitoSOL/SOL rate
+}
+
+#[derive(Debug)]
+pub struct PricePoint {
+    pub timestamp: i64,
+    pub source: PriceSource,
+    pub price: Fraction,
+}
+
+/// Prices over time, replayed relative to the first timestamp so any historical period works.
+pub struct PricePath {
+    pub points: Vec<PricePoint>,
+}
+
+#[derive(Debug)]
+pub struct ReplayRow {
+    pub timestamp: i64,
+    pub obligation: Pubkey,
+    pub ltv: Fraction,
+    pub max_ltv: Fraction,
+    pub liquidation_ltv: Fraction,
+    pub liquidatable: bool,
+}
+
+pub struct ReplayReport {
+    pub rows: Vec<ReplayRow>,
+}
+
+/// A price CSV that can't be replayed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PricePathError {
+    InvalidRow(String), // Not `timestamp,asset,price`, or a field that doesn't parse
+    UnknownAsset(String),
+    Unsorted,
+}
+
+impl fmt::Display for PricePathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PricePathError::InvalidRow(row) => write!(f, "invalid price row: {}", row),
+            PricePathError::UnknownAsset(asset) => write!(f, "unknown asset {}", asset),
+            PricePathError::Unsorted => write!(f, "price rows must be sorted by timestamp"),
+        }
+    }
+}
+
+impl PricePath {
+    /// `timestamp,asset,price` rows, with an optional header line. The asset is a reserve symbol
+    /// or a scope entry index.
+    pub fn from_csv(csv: &str, registry: &ReserveRegistry) -> Result<PricePath, PricePathError> {
+        let points = csv
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("timestamp"))
+            .map(|line| {
+                let invalid_row = || PricePathError::InvalidRow(line.to_string());
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                if fields.len() != 3 {
+                    return Err(invalid_row());
+                }
+
+                let source = match fields[1].parse::<u16>() {
+                    Ok(index) => PriceSource::ScopeEntry(index),
+                    Err(_) => {
+                        let reserve = registry
+                            .iter()
+                            .find(|r| r.symbol.eq_ignore_ascii_case(fields[1]))
+                            .ok_or_else(|| PricePathError::UnknownAsset(fields[1].to_string()))?;
+                        PriceSource::Asset(Asset::Reserve(reserve.address))
+                    }
+                };
+
+                Ok(PricePoint {
+                    timestamp: fields[0].parse().map_err(|_| invalid_row())?,
+                    source,
+                    price: Fraction::from_str(fields[2]).map_err(|_| invalid_row())?,
+                })
+            })
+            .collect::<Result<Vec<PricePoint>, PricePathError>>()?;
+
+        if !points.windows(2).all(|p| p[0].timestamp <= p[1].timestamp) {
+            return Err(PricePathError::Unsorted);
+        }
+
+        Ok(PricePath { points })
+    }
+
+    pub fn from_fixture(filename: &str, registry: &ReserveRegistry) -> PricePath {
+        let data = read_account_data(filename);
+
+        PricePath::from_csv(&String::from_utf8(data).unwrap(), registry)
+            .unwrap_or_else(|e| panic!("{}: {}", filename, e))
+    }
+
+    /// For each timestamp: advances the clock, applies its prices, refreshes reserves and
+    /// obligations, then records the health of every obligation.
+    pub async fn replay(&self, rtc: &RateXTestContext, obligations: &[Pubkey]) -> ReplayReport {
+        let mut rows: Vec<ReplayRow> = vec![];
+        let mut previous = match self.points.first() {
+            Some(point) => point.timestamp,
+            None => return ReplayReport { rows },
+        };
+
+        let mut start = 0;
+        while start < self.points.len() {
+            let timestamp = self.points[start].timestamp;
+            let end = start
+                + self.points[start..]
+                    .iter()
+                    .take_while(|p| p.timestamp == timestamp)
+                    .count();
+            let step = &self.points[start..end];
+            start = end;
+
+            rtc.advance_clock(Duration::from_secs((timestamp - previous) as u64))
+                .await;
+            previous = timestamp;
+
+            for point in step {
+                match point.source {
+                    PriceSource::Asset(asset) => rtc.set_asset_price(asset, point.price).await,
+                    PriceSource::ScopeEntry(index) => {
+                        let now = rtc.get_clock().await.unix_timestamp;
+                        rtc.set_scope_price(index, point.price, now).await
+                    }
+                }
+            }
+
+            rtc.refresh_all(obligations).await;
+
+            for obligation in obligations {
+                let market = read_obligation(&mut rtc.context.borrow_mut(), obligation)
+                    .await
+                    .lending_market;
+                let health = rtc
+                    .lending_market_client(&market)
+                    .obligation_health(obligation)
+                    .await;
+
+                rows.push(ReplayRow {
+                    timestamp,
+                    obligation: *obligation,
+                    ltv: health.ltv,
+                    max_ltv: health.max_ltv,
+                    liquidation_ltv: health.liquidation_ltv,
+                    liquidatable: health.is_liquidatable(),
+                });
+            }
+        }
+
+        ReplayReport { rows }
+    }
+}
+
+impl ReplayReport {
+    pub fn first_liquidation(&self, obligation: &Pubkey) -> Option<i64> {
+        self.rows
+            .iter()
+            .find(|row| row.obligation == *obligation && row.liquidatable)
+            .map(|row| row.timestamp)
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:>12} {:>44} {:>8} {:>8} {:>8} {:>12}\n",
+            "timestamp", "obligation", "ltv", "max", "liq", "liquidatable"
+        );
+        for row in &self.rows {
+            writeln!(
+                table,
+                "{:>12} {:>44} {:>8.4} {:>8.4} {:>8.4} {:>12}",
+                row.timestamp,
+                row.obligation.to_string(),
+                row.ltv.to_num::<f64>(),
+                row.max_ltv.to_num::<f64>(),
+                row.liquidation_ltv.to_num::<f64>(),
+                row.liquidatable
+            )
+            .unwrap();
+        }
+
+        table
+    }
+}
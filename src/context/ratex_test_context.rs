@@ -0,0 +1,261 @@
+#![allow(clippy::too_many_arguments)]
+
+use fixed::types::U68F60 as Fraction;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::ProgramTestContext;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::clock::Clock;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signer;
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use crate::account_diff::AccountSnapshot;
+use crate::helper::{
+    create_payer_from_file, get_account, get_context, get_or_create_associated_token_address,
+    get_sysvar_clock, spl_token_mint, transfer,
+};
+
+use crate::kamino::{Asset, DETACHED_FARMS, KAMINO_MARKETS, KAMINO_RESERVES, KAMINO_SCOPE_PRICES};
+use crate::klend_math::slots_from_seconds;
+use crate::reserve_registry::ReserveRegistry;
+use crate::scope::{OraclePrices, SCOPE_CHAIN_END};
+use spl_token;
+
+use super::{read_obligation, read_reserve, LendingMarketClient, UserTestContext};
+
+pub struct RateXTestContext {
+    pub context: Rc<RefCell<ProgramTestContext>>,
+    pub admin: Keypair,
+    pub users: Vec<UserTestContext>,
+    pub registry: Rc<ReserveRegistry>,
+    pub obligations: Rc<RefCell<Vec<Pubkey>>>,
+}
+
+impl RateXTestContext {
+    pub async fn new() -> RateXTestContext {
+        let registry = Rc::new(ReserveRegistry::from_fixtures(
+            &KAMINO_RESERVES,
+            &DETACHED_FARMS,
+        ));
+        let context = get_context(&registry).await;
+
+        let admin =
+            create_payer_from_file(&mut context.borrow_mut(), "tests/fixtures/admin.json").await;
+
+        // Initialize users
+        let mut users: Vec<UserTestContext> = vec![];
+        users.push(UserTestContext::new_admin_user(context.clone(), registry.clone()).await);
+        for _ in 0..2 {
+            let user = UserTestContext::new(context.clone(), registry.clone()).await;
+            users.push(user);
+        }
+
+        RateXTestContext {
+            context,
+            admin,
+            users,
+            registry,
+            obligations: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    pub fn lending_market_client(&self, market: &Pubkey) -> LendingMarketClient {
+        LendingMarketClient::new(
+            self.context.clone(),
+            self.registry.clone(),
+            market,
+            self.obligations.clone(),
+        )
+    }
+
+    /// Every obligation initialized through a lending market client, closed ones excluded.
+    pub async fn obligations(&self) -> Vec<Pubkey> {
+        let tracked = self.obligations.borrow().clone();
+        let mut obligations = vec![];
+        for obligation in tracked {
+            let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+            if get_account(&mut context.banks_client, obligation)
+                .await
+                .is_some()
+            {
+                obligations.push(obligation);
+            }
+        }
+
+        obligations
+    }
+
+    /// Diff two of these to check what the operations in between changed.
+    pub async fn snapshot(&self, addresses: &[Pubkey]) -> AccountSnapshot {
+        AccountSnapshot::take(&mut self.context.borrow_mut(), addresses).await
+    }
+
+    pub async fn get_clock(&self) -> Clock {
+        let context = &mut self.context.borrow_mut();
+
+        get_sysvar_clock(&mut context.banks_client).await
+    }
+
+    pub async fn warp_to_slot(&self) {
+        let clock: Clock = self.get_clock().await;
+
+        self.context
+            .borrow_mut()
+            .warp_to_slot(clock.slot + 1)
+            .unwrap();
+    }
+
+    pub async fn mint_token(
+        &self,
+        mint: &Pubkey,
+        mint_authority: &Keypair,
+        user: &Keypair,
+        amount: u64,
+    ) {
+        if *mint == spl_token::native_mint::id() {
+            transfer(&mut self.context.borrow_mut(), &user.pubkey(), amount).await;
+            return;
+        }
+
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let user_mint_acc =
+            get_or_create_associated_token_address(context, user, &user.pubkey(), mint).await;
+
+        spl_token_mint(
+            context,
+            &self.admin,
+            mint,
+            &user_mint_acc,
+            mint_authority,
+            amount,
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Mints the liquidity token of the asset reserve to the user, SOL is transferred.
+    pub async fn mint_asset(&self, utc: &UserTestContext, asset: Asset, amount: u64) {
+        let liquidity_mint = self.registry.asset(asset).liquidity_mint;
+
+        self.mint_token(&liquidity_mint, &self.admin, &utc.user, amount)
+            .await;
+    }
+
+    pub async fn get_scope_prices(&self) -> OraclePrices {
+        let context = &mut self.context.borrow_mut();
+        let account = get_account(&mut context.banks_client, KAMINO_SCOPE_PRICES)
+            .await
+            .unwrap();
+
+        OraclePrices::from_account_data(&account.data).unwrap()
+    }
+
+    pub async fn set_scope_prices(&self, prices: &OraclePrices) {
+        let context = &mut self.context.borrow_mut();
+        let mut account = get_account(&mut context.banks_client, KAMINO_SCOPE_PRICES)
+            .await
+            .unwrap();
+        account.data = prices.to_account_data();
+
+        context.set_account(&KAMINO_SCOPE_PRICES, &AccountSharedData::from(account));
+    }
+
+    /// Overrides a single scope entry, e.g. the jitoSOL/SOL rate of the JitoSOL price chain.
+    pub async fn set_scope_price(&self, index: u16, price: Fraction, unix_timestamp: i64) {
+        let clock = self.get_clock().await;
+        let mut prices = self.get_scope_prices().await;
+        prices
+            .set_price(index, price, clock.slot, unix_timestamp as u64)
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        self.set_scope_prices(&prices).await;
+    }
+
+    /// Sets the reserve price in the quote currency by moving the first entry of its price chain,
+    /// the first entry of its TWAP chain moves by the same ratio. Other entries are untouched.
+    pub async fn set_asset_price(&self, asset: Asset, price: Fraction) {
+        let reserve = self.registry.asset(asset);
+        let clock = self.get_clock().await;
+        let mut prices = self.get_scope_prices().await;
+
+        let ratio = price / prices.chain_price(&reserve.scope_price_chain);
+        let mut entries = vec![reserve.scope_price_chain[0], reserve.scope_twap_chain[0]];
+        entries.dedup();
+        for index in entries.into_iter().filter(|i| *i != SCOPE_CHAIN_END) {
+            let entry = prices.price(index) * ratio;
+            prices
+                .set_price(index, entry, clock.slot, clock.unix_timestamp as u64)
+                .unwrap_or_else(|e| panic!("{}", e));
+        }
+
+        self.set_scope_prices(&prices).await;
+    }
+
+    /// Moves the clock forward by `duration` (slot and unix timestamp), stamps the scope prices
+    /// with the new time and refreshes every registered reserve, then every open obligation.
+    pub async fn advance_time(&self, duration: Duration) {
+        self.advance_clock(duration).await;
+        self.refresh_all(&self.obligations().await).await;
+    }
+
+    /// Moves the clock forward and stamps the scope prices with the new time, nothing is refreshed.
+    pub async fn advance_clock(&self, duration: Duration) {
+        let mut clock = self.get_clock().await;
+        let slots = slots_from_seconds(duration.as_secs());
+        clock.slot += slots;
+        clock.unix_timestamp += duration.as_secs() as i64;
+        {
+            let context = &mut self.context.borrow_mut();
+            if slots > 0 {
+                context.warp_to_slot(clock.slot).unwrap();
+            }
+            context.set_sysvar::<Clock>(&clock);
+        }
+
+        let mut prices = self.get_scope_prices().await;
+        prices.refresh_timestamps(clock.slot, clock.unix_timestamp as u64);
+        self.set_scope_prices(&prices).await;
+    }
+
+    /// Refreshes every registered reserve, then the given obligations. Panics on a reserve
+    /// fixture last updated after the current slot, klend can't refresh it before a warp past it.
+    pub async fn refresh_all(&self, obligations: &[Pubkey]) {
+        let slot = self.get_clock().await.slot;
+        for market in KAMINO_MARKETS {
+            let client = self.lending_market_client(&market);
+            for reserve in client.reserves() {
+                let state = read_reserve(&mut self.context.borrow_mut(), &reserve).await;
+                assert!(
+                    state.last_update.slot <= slot,
+                    "reserve {} was last updated at slot {}, warp past it from slot {}",
+                    reserve,
+                    state.last_update.slot,
+                    slot
+                );
+
+                client
+                    .refresh_reserve(&self.admin, Asset::Reserve(reserve))
+                    .await;
+            }
+        }
+
+        for obligation in obligations {
+            let market = read_obligation(&mut self.context.borrow_mut(), obligation)
+                .await
+                .lending_market;
+            self.lending_market_client(&market)
+                .refresh_obligation(&self.admin, obligation)
+                .await;
+        }
+    }
+
+    pub async fn set_sysvar_clock(&self, time: i64) {
+        let mut clock: Clock = get_sysvar_clock(&mut self.context.borrow_mut().banks_client).await;
+
+        clock.epoch_start_timestamp = time;
+        clock.unix_timestamp = time;
+        clock.slot = 298271839;
+
+        self.context.borrow_mut().set_sysvar::<Clock>(&clock);
+    }
+}
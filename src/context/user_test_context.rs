@@ -0,0 +1,648 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::accounts::{format_fields, obligation_fields};
+use crate::helper::{
+    create_token_account, create_user, get_account, get_associated_token_address, get_keypair,
+    get_or_create_associated_token_address, get_token_balance, process_instructions,
+    process_instructions_with_budget, transfer, ComputeBudget,
+};
+use crate::kamino::{
+    compose_klend_borrow_obligation_liquidity_ix, compose_klend_deposit_obligation_collateral_ix,
+    compose_klend_deposit_reserve_liquidity_ix, compose_klend_flash_borrow_ix,
+    compose_klend_flash_repay_ix, compose_klend_init_referrer_state_and_short_url_ix,
+    compose_klend_init_user_metadata_ix, compose_klend_redeem_reserve_collateral_ix,
+    compose_klend_refresh_obligation_ix, compose_klend_refresh_reserve_ix,
+    compose_klend_repay_obligation_liquidity_ix, compose_klend_withdraw_obligation_collateral_ix,
+    compose_mock_swap_jitosol_to_sol_ix, compose_mock_swap_sol_to_jitosol_ix,
+    lending_market_authority, mock_swap_output, Asset, JITOSOL_MINT, KLEND_PROGRAM_ID, MAIN_MARKET,
+    MOCK_SWAP_RATE,
+};
+use crate::klend_math::{
+    collateral_to_liquidity, liquidity_to_collateral, market_value, obligation_debt,
+};
+use crate::lookup_table::compose_create_lookup_table_ix;
+use crate::reserve_registry::ReserveRegistry;
+use crate::tx_report::TxReport;
+use borsh::BorshDeserialize;
+use fixed::types::U68F60 as Fraction;
+use klend::{Obligation, ObligationLiquidity};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::ProgramTestContext;
+use solana_sdk::account::Account;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signer;
+use spl_token;
+use std::{cell::RefCell, future::Future, rc::Rc};
+
+use super::{read_obligation, read_projected_reserve, LendingMarketClient};
+
+pub struct UserTestContext {
+    pub context: Rc<RefCell<ProgramTestContext>>,
+    pub admin: Keypair,
+    pub user: Keypair,
+    pub registry: Rc<ReserveRegistry>,
+}
+
+impl UserTestContext {
+    pub async fn new(
+        context: Rc<RefCell<ProgramTestContext>>,
+        registry: Rc<ReserveRegistry>,
+    ) -> UserTestContext {
+        let admin = get_keypair("tests/fixtures/admin.json").await;
+
+        let user = create_user(&mut context.borrow_mut()).await;
+
+        UserTestContext {
+            context,
+            admin,
+            user,
+            registry,
+        }
+    }
+
+    pub async fn new_admin_user(
+        context: Rc<RefCell<ProgramTestContext>>,
+        registry: Rc<ReserveRegistry>,
+    ) -> UserTestContext {
+        let admin = get_keypair("tests/fixtures/admin.json").await;
+
+        UserTestContext {
+            context,
+            admin: admin.insecure_clone(),
+            user: admin,
+            registry,
+        }
+    }
+
+    pub async fn get_account(&self, account_pubkey: Pubkey) -> Account {
+        self.context
+            .borrow_mut()
+            .banks_client
+            .get_account(account_pubkey)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    pub async fn balance(&self) -> u64 {
+        self.get_account(self.user.pubkey()).await.lamports
+    }
+
+    pub async fn mint_balance(&self, mint: &Pubkey) -> u64 {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let account = get_associated_token_address(&self.user.pubkey(), mint).await;
+        let balance = get_token_balance(&mut context.banks_client, account).await;
+
+        balance
+    }
+
+    pub async fn assert_mint_balance(&self, mint: Pubkey, expect: u64) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let account = get_associated_token_address(&self.user.pubkey(), &mint).await;
+        let balance = get_token_balance(&mut context.banks_client, account).await;
+
+        assert_eq!(balance, expect);
+    }
+
+    /// Also creates the user lookup table recorded in the metadata, see `extend_lookup_table`.
+    pub async fn klend_init_user_metadata(&self) -> Pubkey {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let (user_metadata, _) = Pubkey::find_program_address(
+            &[b"user_meta", &self.user.pubkey().to_bytes()],
+            &KLEND_PROGRAM_ID,
+        );
+
+        let (create_lookup_table_ix, user_lookup_table) =
+            compose_create_lookup_table_ix(context, &self.user.pubkey()).await;
+
+        let instruction = compose_klend_init_user_metadata_ix(
+            &self.user.pubkey(),
+            &self.user.pubkey(),
+            &user_metadata,
+            &user_lookup_table,
+        );
+
+        process_instructions(context, &self.user, &[create_lookup_table_ix, instruction]).await;
+
+        user_lookup_table
+    }
+
+    /// Makes the user a referrer, it needs a user metadata first.
+    pub async fn klend_init_referrer_state_and_short_url(&self, short_url: &str) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let instruction =
+            compose_klend_init_referrer_state_and_short_url_ix(&self.user.pubkey(), short_url);
+
+        process_instructions(context, &self.user, &[instruction]).await;
+    }
+
+    pub async fn mock_swap_sol_to_jitosol(&self, amount: u64) {
+        assert_eq!(self.user.pubkey(), self.admin.pubkey());
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+
+        let user_wsol_account = Keypair::new();
+        create_token_account(
+            context,
+            &self.user,
+            &user_wsol_account,
+            &spl_token::native_mint::id(),
+            &self.user.pubkey(),
+            amount,
+        )
+        .await
+        .unwrap();
+
+        let temp_destination_account = Keypair::new();
+        create_token_account(
+            context,
+            &self.user,
+            &temp_destination_account,
+            &spl_token::native_mint::id(),
+            &self.user.pubkey(),
+            0,
+        )
+        .await
+        .unwrap();
+
+        let user_jitosol_account = get_or_create_associated_token_address(
+            context,
+            &self.user,
+            &self.user.pubkey(),
+            &JITOSOL_MINT,
+        )
+        .await;
+
+        let instructions = compose_mock_swap_sol_to_jitosol_ix(
+            &self.user.pubkey(),
+            &user_wsol_account.pubkey(),
+            &temp_destination_account.pubkey(),
+            &user_jitosol_account,
+            amount,
+            MOCK_SWAP_RATE,
+        );
+
+        process_instructions(context, &self.user, &instructions).await;
+    }
+
+    /// Flash borrows `borrow_amount` sol and swaps it to jitosol, deposits it together with the
+    /// jitosol balance of the user as collateral, then borrows the sol back to repay the flash loan.
+    pub async fn enter_leverage_borrow(&self, obligation: &Pubkey, borrow_amount: u64) -> TxReport {
+        assert_eq!(self.user.pubkey(), self.admin.pubkey()); // must be mint authority of jitosol for ease of test
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let lending_market_authority = lending_market_authority(&MAIN_MARKET);
+        let jitosol = self.registry.asset(Asset::JitoSol);
+        let sol = self.registry.asset(Asset::Sol);
+
+        let budget = ComputeBudget::auto_sized();
+        let mut instructions: Vec<Instruction> = vec![];
+
+        // 1. Flash borrow sol
+        let user_destination_liquidity = Keypair::new();
+        create_token_account(
+            context,
+            &self.user,
+            &user_destination_liquidity,
+            &sol.liquidity_mint,
+            &self.user.pubkey(),
+            0,
+        )
+        .await
+        .unwrap();
+
+        instructions.push(compose_klend_flash_borrow_ix(
+            &self.user.pubkey(),
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &sol.address,
+            &sol.liquidity_mint,
+            &sol.liquidity_supply_vault,
+            &user_destination_liquidity.pubkey(),
+            &sol.liquidity_fee_vault,
+            borrow_amount,
+        ));
+
+        // 2. Swap sol to jitosol
+        let temp_destination_account = Keypair::new();
+        create_token_account(
+            context,
+            &self.user,
+            &temp_destination_account,
+            &sol.liquidity_mint,
+            &self.user.pubkey(),
+            0,
+        )
+        .await
+        .unwrap();
+
+        let user_jitosol_account = get_or_create_associated_token_address(
+            context,
+            &self.user,
+            &self.user.pubkey(),
+            &jitosol.liquidity_mint,
+        )
+        .await;
+
+        instructions.extend_from_slice(&compose_mock_swap_sol_to_jitosol_ix(
+            &self.user.pubkey(),
+            &user_destination_liquidity.pubkey(),
+            &temp_destination_account.pubkey(),
+            &user_jitosol_account,
+            borrow_amount,
+            MOCK_SWAP_RATE,
+        ));
+
+        // 3. Deposit jitosol to reserve
+        let deposit_amount = get_token_balance(&mut context.banks_client, user_jitosol_account)
+            .await
+            + mock_swap_output(borrow_amount, MOCK_SWAP_RATE);
+        let user_jitosol_collateral_account = get_or_create_associated_token_address(
+            context,
+            &self.user,
+            &self.user.pubkey(),
+            &jitosol.collateral_mint,
+        )
+        .await;
+
+        instructions.push(compose_klend_deposit_reserve_liquidity_ix(
+            &self.user.pubkey(),
+            &jitosol.address,
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &jitosol.liquidity_mint,
+            &jitosol.liquidity_supply_vault,
+            &jitosol.collateral_mint,
+            &user_jitosol_account,
+            &user_jitosol_collateral_account,
+            deposit_amount,
+        ));
+
+        // 4. Deposit obligation collateral, minted at the exchange rate of the slot the transaction runs in
+        let jitosol_reserve = read_projected_reserve(context, &jitosol.address).await;
+        let collateral_amount = liquidity_to_collateral(&jitosol_reserve, deposit_amount);
+        instructions.push(compose_klend_refresh_reserve_ix(jitosol));
+
+        instructions.push(compose_klend_refresh_obligation_ix(
+            obligation,
+            &MAIN_MARKET,
+            &[],
+        ));
+
+        instructions.push(compose_klend_deposit_obligation_collateral_ix(
+            &self.user.pubkey(),
+            obligation,
+            &MAIN_MARKET,
+            &jitosol.address,
+            &jitosol.collateral_supply_vault,
+            &user_jitosol_collateral_account,
+            collateral_amount,
+        ));
+
+        // 5. Borrow obligation liquidity
+        instructions.push(compose_klend_refresh_reserve_ix(jitosol));
+
+        instructions.push(compose_klend_refresh_reserve_ix(sol));
+
+        instructions.push(compose_klend_refresh_obligation_ix(
+            obligation,
+            &MAIN_MARKET,
+            &[jitosol.address],
+        ));
+
+        instructions.push(compose_klend_borrow_obligation_liquidity_ix(
+            &self.user.pubkey(),
+            obligation,
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &sol.address,
+            &sol.liquidity_mint,
+            &sol.liquidity_supply_vault,
+            &sol.liquidity_fee_vault,
+            &user_destination_liquidity.pubkey(),
+            borrow_amount,
+        ));
+
+        // 6. Flash repay sol
+        instructions.push(compose_klend_flash_repay_ix(
+            &self.user.pubkey(),
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &sol.address,
+            &sol.liquidity_mint,
+            &sol.liquidity_supply_vault,
+            &user_destination_liquidity.pubkey(), // Must be the same one of borrow ix
+            &sol.liquidity_fee_vault,
+            borrow_amount,
+            budget.instruction_count() as u8,
+        ));
+
+        process_instructions_with_budget(context, &self.user, &instructions, budget).await
+    }
+
+    /// Repays the whole sol debt with a flash loan, withdraws and redeems all jitosol collateral,
+    /// then swaps enough jitosol back to sol to repay the flash loan.
+    pub async fn leave_leverage_borrow(&self, obligation: &Pubkey) {
+        assert_eq!(self.user.pubkey(), self.admin.pubkey()); // must be mint authority of jitosol for ease of test
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let lending_market_authority = lending_market_authority(&MAIN_MARKET);
+        let jitosol = self.registry.asset(Asset::JitoSol);
+        let sol = self.registry.asset(Asset::Sol);
+
+        // Debt accrued up to the slot the transaction runs in, collateral tokens don't accrue
+        let obligation_state = read_obligation(context, obligation).await;
+        let collateral_amount = obligation_state
+            .deposits
+            .iter()
+            .find(|d| d.deposit_reserve == jitosol.address)
+            .unwrap()
+            .deposited_amount;
+        let sol_reserve = read_projected_reserve(context, &sol.address).await;
+        let debt_amount: u64 = obligation_debt(
+            obligation_state
+                .borrows
+                .iter()
+                .find(|b| b.borrow_reserve == sol.address)
+                .unwrap(),
+            &sol_reserve,
+        )
+        .ceil()
+        .to_num();
+
+        let budget = ComputeBudget::default();
+        let mut instructions: Vec<Instruction> = vec![];
+
+        // 1. Flash borrow sol
+        let user_source_liquidity = Keypair::new();
+        create_token_account(
+            context,
+            &self.user,
+            &user_source_liquidity,
+            &sol.liquidity_mint,
+            &self.user.pubkey(),
+            0,
+        )
+        .await
+        .unwrap();
+
+        instructions.push(compose_klend_flash_borrow_ix(
+            &self.user.pubkey(),
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &sol.address,
+            &sol.liquidity_mint,
+            &sol.liquidity_supply_vault,
+            &user_source_liquidity.pubkey(),
+            &sol.liquidity_fee_vault,
+            debt_amount,
+        ));
+
+        // 2. Repay the whole obligation liquidity
+        instructions.push(compose_klend_refresh_reserve_ix(jitosol));
+
+        instructions.push(compose_klend_refresh_reserve_ix(sol));
+
+        instructions.push(compose_klend_refresh_obligation_ix(
+            obligation,
+            &MAIN_MARKET,
+            &[jitosol.address, sol.address],
+        ));
+
+        instructions.push(compose_klend_repay_obligation_liquidity_ix(
+            &self.user.pubkey(),
+            obligation,
+            &MAIN_MARKET,
+            &sol.address,
+            &sol.liquidity_mint,
+            &sol.liquidity_supply_vault,
+            &user_source_liquidity.pubkey(),
+            u64::MAX,
+        ));
+
+        // 3. Withdraw obligation collateral, the sol borrow is gone after the full repay
+        let user_destination_collateral =
+            get_associated_token_address(&self.user.pubkey(), &jitosol.collateral_mint).await;
+        instructions.push(compose_klend_refresh_reserve_ix(jitosol));
+
+        instructions.push(compose_klend_refresh_obligation_ix(
+            obligation,
+            &MAIN_MARKET,
+            &[jitosol.address],
+        ));
+
+        instructions.push(compose_klend_withdraw_obligation_collateral_ix(
+            &self.user.pubkey(),
+            obligation,
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &jitosol.address,
+            &jitosol.collateral_supply_vault,
+            &user_destination_collateral,
+            u64::MAX,
+        ));
+
+        // 4. Redeem reserve jitosol liquidity
+        let user_destination_liquidity = get_or_create_associated_token_address(
+            context,
+            &self.user,
+            &self.user.pubkey(),
+            &jitosol.liquidity_mint,
+        )
+        .await;
+
+        instructions.push(compose_klend_redeem_reserve_collateral_ix(
+            &self.user.pubkey(),
+            &jitosol.address,
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &jitosol.liquidity_mint,
+            &jitosol.liquidity_supply_vault,
+            &jitosol.collateral_mint,
+            &user_destination_collateral,
+            &user_destination_liquidity,
+            collateral_amount,
+        ));
+
+        // 5. Swap some jitosol to sol
+        transfer(context, &self.user.pubkey(), debt_amount).await;
+
+        let temp_source_account = Keypair::new();
+        create_token_account(
+            context,
+            &self.user,
+            &temp_source_account,
+            &sol.liquidity_mint,
+            &self.user.pubkey(),
+            debt_amount,
+        )
+        .await
+        .unwrap();
+
+        instructions.extend_from_slice(&compose_mock_swap_jitosol_to_sol_ix(
+            &self.user.pubkey(),
+            &user_source_liquidity.pubkey(),
+            &temp_source_account.pubkey(),
+            &user_destination_liquidity,
+            debt_amount,
+            MOCK_SWAP_RATE,
+        ));
+
+        // 6. Flash repay sol
+        instructions.push(compose_klend_flash_repay_ix(
+            &self.user.pubkey(),
+            &MAIN_MARKET,
+            &lending_market_authority,
+            &sol.address,
+            &sol.liquidity_mint,
+            &sol.liquidity_supply_vault,
+            &user_source_liquidity.pubkey(), // Must be the same one of borrow ix
+            &sol.liquidity_fee_vault,
+            debt_amount,
+            budget.instruction_count() as u8,
+        ));
+
+        process_instructions_with_budget(context, &self.user, &instructions, budget).await;
+    }
+
+    /// Liquidates another user's unhealthy obligation, see `LiquidationOutcome`.
+    pub async fn klend_liquidate(
+        &self,
+        client: &LendingMarketClient,
+        obligation: &Pubkey,
+        repay: Asset,
+        withdraw: Asset,
+        liquidity_amount: u64,
+    ) -> LiquidationOutcome {
+        let liquidation = client.liquidate_obligation_and_redeem_reserve_collateral(
+            &self.user,
+            obligation,
+            repay,
+            withdraw,
+            liquidity_amount,
+            0,
+        );
+
+        self.measure_liquidation(client, obligation, repay, withdraw, liquidation)
+            .await
+    }
+
+    /// Runs `liquidation` and compares the obligation and this user's balance around it.
+    pub async fn measure_liquidation(
+        &self,
+        client: &LendingMarketClient,
+        obligation: &Pubkey,
+        repay: Asset,
+        withdraw: Asset,
+        liquidation: impl Future<Output = ()>,
+    ) -> LiquidationOutcome {
+        let repay_reserve = client.reserve(repay).address;
+        let withdraw_reserve = client.reserve(withdraw);
+
+        let (debt_before, deposit_before) = {
+            let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+            get_or_create_associated_token_address(
+                context,
+                &self.user,
+                &self.user.pubkey(),
+                &withdraw_reserve.liquidity_mint,
+            )
+            .await;
+
+            let state = read_obligation(context, obligation).await;
+            let reserve = read_projected_reserve(context, &repay_reserve).await;
+            (
+                obligation_debt(obligation_borrow(&state, &repay_reserve), &reserve),
+                obligation_deposit(&state, &withdraw_reserve.address),
+            )
+        };
+        let balance_before = self.mint_balance(&withdraw_reserve.liquidity_mint).await;
+
+        liquidation.await;
+
+        let state = client.get_obligation(obligation).await;
+        let repay_state = client.get_reserve_account(repay).await;
+        let withdraw_state = client.get_reserve_account(withdraw).await;
+        // A fully repaid borrow is removed from the obligation
+        let debt_after = state
+            .borrows
+            .iter()
+            .find(|b| b.borrow_reserve == repay_reserve)
+            .map(|b| Fraction::from_bits(b.borrowed_amount_sf))
+            .unwrap_or(Fraction::ZERO);
+        let received_amount =
+            self.mint_balance(&withdraw_reserve.liquidity_mint).await - balance_before;
+
+        let repaid_amount: u64 = (debt_before - debt_after).round().to_num();
+        let withdrawn_collateral =
+            deposit_before - obligation_deposit(&state, &withdraw_reserve.address);
+        let repaid_value = market_value(&repay_state, repaid_amount);
+        let withdrawn_value = market_value(
+            &withdraw_state,
+            collateral_to_liquidity(&withdraw_state, withdrawn_collateral),
+        );
+
+        LiquidationOutcome {
+            repaid_amount,
+            withdrawn_collateral,
+            received_amount,
+            repaid_value,
+            withdrawn_value,
+            received_value: market_value(&withdraw_state, received_amount),
+            bonus: withdrawn_value
+                .checked_div(repaid_value)
+                .and_then(|ratio| ratio.checked_sub(Fraction::ONE)),
+        }
+    }
+
+    pub async fn dump_obligation(&self, address: &Pubkey) {
+        let context: &mut ProgramTestContext = &mut self.context.borrow_mut();
+        let account = get_account(&mut context.banks_client, *address)
+            .await
+            .unwrap();
+
+        let obligation = Obligation::try_from_slice(&account.data[8..]).unwrap();
+        print!(
+            "obligation {}\n{}",
+            address,
+            format_fields(&obligation_fields(&obligation))
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct LiquidationOutcome {
+    /// Debt liquidity of the obligation actually repaid, capped by the close factor
+    pub repaid_amount: u64,
+    /// Collateral tokens taken out of the obligation
+    pub withdrawn_collateral: u64,
+    /// Redeemed collateral liquidity received, net of the protocol liquidation fee
+    pub received_amount: u64,
+    pub repaid_value: Fraction,
+    /// What the obligation loses is withdrawn value - repaid value
+    pub withdrawn_value: Fraction,
+    /// The protocol keeps withdrawn value - received value
+    pub received_value: Fraction,
+    /// Liquidation bonus, withdrawn value / repaid value - 1. None if nothing was repaid or the
+    /// withdrawn value is below the repaid value (bad debt).
+    pub bonus: Option<Fraction>,
+}
+
+fn obligation_borrow<'a>(obligation: &'a Obligation, reserve: &Pubkey) -> &'a ObligationLiquidity {
+    obligation
+        .borrows
+        .iter()
+        .find(|b| b.borrow_reserve == *reserve)
+        .unwrap_or_else(|| panic!("obligation has no borrow in reserve {}", reserve))
+}
+
+fn obligation_deposit(obligation: &Obligation, reserve: &Pubkey) -> u64 {
+    obligation
+        .deposits
+        .iter()
+        .find(|d| d.deposit_reserve == *reserve)
+        .map(|d| d.deposited_amount)
+        .unwrap_or(0)
+}
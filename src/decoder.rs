@@ -0,0 +1,253 @@
+//! Decodes klend and kfarm instructions back to their name, args and the role of each account,
+//! the inverse of the `compose_klend_*` builders.
+
+use crate::{kfarm_instructions, klend_instructions};
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::transaction::VersionedTransaction;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedAccount {
+    pub meta: AccountMeta,
+    /// Account name in the IDL (obligation, reserve, lending_market_authority, ...), remaining
+    /// accounts are `remaining[i]` and accounts of other programs `#i`
+    pub role: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedInstruction {
+    pub program_id: Pubkey,
+    /// "klend" or "kfarm", None for other programs
+    pub program: Option<&'static str>,
+    /// None for other programs and unknown discriminators
+    pub name: Option<&'static str>,
+    /// Debug formatted args
+    pub args: Option<String>,
+    pub accounts: Vec<DecodedAccount>,
+}
+
+/// A message that can't be decoded, its account indexes or header don't match its keys.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    MissingLookupTable(Pubkey),
+    LookupIndexOutOfRange { table: Pubkey, index: u8 },
+    AccountIndexOutOfRange { index: u8, account_count: usize },
+    InvalidHeader,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::MissingLookupTable(key) => {
+                write!(f, "lookup table {} is needed to decode the message", key)
+            }
+            DecodeError::LookupIndexOutOfRange { table, index } => {
+                write!(f, "index {} is out of lookup table {}", index, table)
+            }
+            DecodeError::AccountIndexOutOfRange {
+                index,
+                account_count,
+            } => write!(
+                f,
+                "account index {} is out of the {} message accounts",
+                index, account_count
+            ),
+            DecodeError::InvalidHeader => {
+                write!(f, "message header doesn't match its account keys")
+            }
+        }
+    }
+}
+
+impl DecodedInstruction {
+    pub fn account(&self, role: &str) -> Option<&Pubkey> {
+        self.accounts
+            .iter()
+            .find(|account| account.role == role)
+            .map(|account| &account.meta.pubkey)
+    }
+}
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.program, self.name) {
+            (Some(program), Some(name)) => writeln!(
+                f,
+                "{} {} {}",
+                program,
+                name,
+                self.args.as_deref().unwrap_or_default()
+            )?,
+            (Some(program), None) => writeln!(f, "{} unknown instruction", program)?,
+            _ => writeln!(f, "{}", self.program_id)?,
+        }
+
+        for account in &self.accounts {
+            writeln!(
+                f,
+                "    {:<40} {:<44} {}{}",
+                account.role,
+                account.meta.pubkey.to_string(),
+                if account.meta.is_signer { "s" } else { "-" },
+                if account.meta.is_writable { "w" } else { "-" }
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn decode_instruction(instruction: &Instruction) -> DecodedInstruction {
+    let (program, idl_instruction) = if instruction.program_id == klend_instructions::ID {
+        (
+            Some("klend"),
+            klend_instructions::decode(&instruction.data)
+                .map(|ix| (ix.name, ix.account_names, ix.args)),
+        )
+    } else if instruction.program_id == kfarm_instructions::ID {
+        (
+            Some("kfarm"),
+            kfarm_instructions::decode(&instruction.data)
+                .map(|ix| (ix.name, ix.account_names, ix.args)),
+        )
+    } else {
+        (None, None)
+    };
+
+    let accounts = instruction
+        .accounts
+        .iter()
+        .enumerate()
+        .map(|(i, meta)| {
+            let role = match &idl_instruction {
+                Some((_, account_names, _)) => match account_names.get(i) {
+                    Some(name) => name.to_string(),
+                    None => format!("remaining[{}]", i - account_names.len()),
+                },
+                None => format!("#{}", i),
+            };
+
+            DecodedAccount {
+                meta: meta.clone(),
+                role,
+            }
+        })
+        .collect();
+
+    let (name, args) = match idl_instruction {
+        Some((name, _, args)) => (Some(name), Some(args)),
+        None => (None, None),
+    };
+
+    DecodedInstruction {
+        program_id: instruction.program_id,
+        program,
+        name,
+        args,
+        accounts,
+    }
+}
+
+/// Decodes every instruction of the transaction, the lookup tables of a v0 message must be given.
+pub fn decode_transaction(
+    transaction: &VersionedTransaction,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<Vec<DecodedInstruction>, DecodeError> {
+    Ok(message_instructions(&transaction.message, lookup_tables)?
+        .iter()
+        .map(decode_instruction)
+        .collect())
+}
+
+/// Rebuilds the instructions of a compiled message, resolving lookup table indexes.
+pub fn message_instructions(
+    message: &VersionedMessage,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<Vec<Instruction>, DecodeError> {
+    let mut keys: Vec<AccountMeta> = static_account_metas(message)?;
+
+    // Loaded addresses follow the static ones, writable ones of every table first
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let address = |key: &Pubkey, index: u8| {
+        let table = lookup_tables
+            .iter()
+            .find(|table| table.key == *key)
+            .ok_or(DecodeError::MissingLookupTable(*key))?;
+        table
+            .addresses
+            .get(index as usize)
+            .copied()
+            .ok_or(DecodeError::LookupIndexOutOfRange { table: *key, index })
+    };
+    for lookup in lookups {
+        for index in &lookup.writable_indexes {
+            keys.push(AccountMeta::new(
+                address(&lookup.account_key, *index)?,
+                false,
+            ));
+        }
+    }
+    for lookup in lookups {
+        for index in &lookup.readonly_indexes {
+            keys.push(AccountMeta::new_readonly(
+                address(&lookup.account_key, *index)?,
+                false,
+            ));
+        }
+    }
+
+    let key = |index: u8| {
+        keys.get(index as usize)
+            .cloned()
+            .ok_or(DecodeError::AccountIndexOutOfRange {
+                index,
+                account_count: keys.len(),
+            })
+    };
+    message
+        .instructions()
+        .iter()
+        .map(|compiled| {
+            Ok(Instruction {
+                program_id: key(compiled.program_id_index)?.pubkey,
+                accounts: compiled
+                    .accounts
+                    .iter()
+                    .map(|index| key(*index))
+                    .collect::<Result<_, _>>()?,
+                data: compiled.data.clone(),
+            })
+        })
+        .collect()
+}
+
+fn static_account_metas(message: &VersionedMessage) -> Result<Vec<AccountMeta>, DecodeError> {
+    let header = message.header();
+    let keys = message.static_account_keys();
+    let signers = header.num_required_signatures as usize;
+    let writable_signers = signers
+        .checked_sub(header.num_readonly_signed_accounts as usize)
+        .ok_or(DecodeError::InvalidHeader)?;
+    let writable_unsigned = keys
+        .len()
+        .checked_sub(header.num_readonly_unsigned_accounts as usize)
+        .filter(|_| signers <= keys.len())
+        .ok_or(DecodeError::InvalidHeader)?;
+
+    Ok(keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| AccountMeta {
+            pubkey: *key,
+            is_signer: i < signers,
+            is_writable: if i < signers {
+                i < writable_signers
+            } else {
+                i < writable_unsigned
+            },
+        })
+        .collect())
+}
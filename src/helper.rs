@@ -0,0 +1,804 @@
+use std::cell::RefCell;
+
+use super::account_diff::AccountSnapshot;
+use super::kamino::{
+    lending_market_authority, EXAMPLE_OBLIGATION, KAMINO_MARKETS, KAMINO_SCOPE_PRICES,
+    KFARM_PROGRAM_ID, KLEND_PROGRAM_ID, KLEND_SCOPE_PRICES_PROGRAM_ID,
+};
+use super::reserve_registry::{ReserveInfo, ReserveRegistry};
+use super::reserve_snapshot::ReserveSnapshot;
+use super::tx_report::TxReport;
+use super::tx_size::TxSize;
+use crate::accounts::{format_fields, reserve_fields};
+use borsh::{BorshDeserialize, BorshSerialize};
+use klend::state::Reserve;
+use solana_sdk::clock::Clock;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::program_option::COption;
+use solana_sdk::rent::Rent;
+use solana_sdk::system_program;
+use spl_associated_token_account;
+use spl_token::state::{Account as TokenAccount, AccountState};
+use spl_token::{self, state::Mint};
+
+use solana_program_test::{
+    find_file, read_file, BanksClient, BanksClientError, ProgramTest, ProgramTestContext,
+};
+
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::hash::Hash;
+use solana_program::message::{v0, Message, VersionedMessage};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::{
+    account::Account, instruction::Instruction, program_pack::Pack, signature::read_keypair_file,
+    signature::Keypair, signature::Signer, system_instruction, sysvar, transaction::Transaction,
+    transaction::VersionedTransaction, transport::TransportError,
+};
+
+use std::rc::Rc;
+
+pub const SSOL_MINT: Pubkey = pubkey!("sSo14endRuUbvQaJS3dq36Q829a3A6BEfoeeRGJywEh");
+pub const ENDOAVS_SSOL_VAULT: Pubkey = pubkey!("Bc7hj6aFhBRihZ8dYp8qXWbuDBXYMya4dzFGmHezLnB7");
+pub const ENDOAVS_SONIC: Pubkey = pubkey!("HBkJwH6rjUUBK1wNhBuYgo9Wnk1iCx2phduyxWCQj6uk");
+pub const SONIC_SSOL: Pubkey = pubkey!("sonickAJFiVLcYXx25X9vpF293udaWqDMUCiGtk7dg2");
+
+pub fn read_account_data(filename: &str) -> Vec<u8> {
+    read_file(find_file(filename).unwrap_or_else(|| {
+        panic!("Unable to load {}", filename);
+    }))
+}
+
+pub fn add_mint(
+    pt: &mut ProgramTest,
+    mint_address: Pubkey,
+    mint_account_file: &str,
+    mint_authority: Option<Pubkey>,
+) {
+    let mint_data = read_file(find_file(mint_account_file).unwrap_or_else(|| {
+        panic!("Unable to load {}", mint_account_file);
+    }));
+
+    let mut mint = Mint::unpack_from_slice(&mint_data).unwrap();
+    if let Some(auth) = mint_authority {
+        mint.mint_authority = COption::Some(auth);
+    }
+    let mut fixed_data: Vec<u8> = vec![0; 82];
+    Mint::pack(mint, &mut fixed_data).unwrap();
+
+    pt.add_account(
+        mint_address,
+        Account {
+            lamports: 1461600,
+            data: fixed_data,
+            owner: spl_token::ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+pub fn load_kamino_fixtures(
+    pt: &mut ProgramTest,
+    registry: &ReserveRegistry,
+    mint_authority: &Pubkey,
+) {
+    // pt.add_program("klend", KLEND_PROGRAM_ID, None);
+    pt.add_program("kamino_lending", KLEND_PROGRAM_ID, None);
+    pt.add_program("kfarm", KFARM_PROGRAM_ID, None);
+    pt.add_program("klend_refresh_price", KLEND_SCOPE_PRICES_PROGRAM_ID, None);
+
+    for market in KAMINO_MARKETS {
+        pt.add_account_with_file_data(
+            market,
+            LAMPORTS_PER_SOL,
+            KLEND_PROGRAM_ID,
+            &format!("{}.bin", market),
+        );
+
+        pt.add_account(
+            lending_market_authority(&market),
+            Account {
+                lamports: LAMPORTS_PER_SOL * 10,
+                data: vec![],
+                owner: system_program::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    for reserve in registry.iter() {
+        load_reserve_fixtures(pt, reserve, mint_authority);
+    }
+
+    // Kamino scope prices
+    pt.add_account_with_file_data(
+        KAMINO_SCOPE_PRICES,
+        LAMPORTS_PER_SOL,
+        KLEND_SCOPE_PRICES_PROGRAM_ID,
+        "3NJYftD5sjVfxSnUdZ1wVML8f3aC6mp1CXCL6L7TnU8C.bin",
+    );
+
+    pt.add_account_with_file_data(
+        EXAMPLE_OBLIGATION,
+        LAMPORTS_PER_SOL,
+        KLEND_PROGRAM_ID,
+        "4w1qUuYbXpwYkqWzuovJFvP3TRt9EgEzYQv7kYY1nM3J.bin",
+    );
+}
+
+fn fixture_file(address: &Pubkey) -> Option<String> {
+    let filename = format!("{}.bin", address);
+
+    find_file(&filename).map(|_| filename)
+}
+
+/// Loads a reserve and every account it references. Mints and token accounts without a
+/// fixture file are synthesized from the reserve state.
+fn load_reserve_fixtures(pt: &mut ProgramTest, reserve: &ReserveInfo, mint_authority: &Pubkey) {
+    let mut data = read_account_data(&format!("{}.bin", reserve.address));
+    let mut state = Reserve::try_from_slice(&data[8..]).unwrap();
+
+    // Keep the on-chain reserve consistent with the registry, which detached some farms
+    if state.farm_collateral != reserve.farm_collateral || state.farm_debt != reserve.farm_debt {
+        state.farm_collateral = reserve.farm_collateral;
+        state.farm_debt = reserve.farm_debt;
+        data.truncate(8);
+        data.extend(state.try_to_vec().unwrap());
+    }
+
+    pt.add_account(
+        reserve.address,
+        Account {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: KLEND_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    let lending_market_authority = lending_market_authority(&reserve.lending_market);
+
+    // Liquidity mint, take over the mint authority so that tests can mint any reserve asset
+    if reserve.liquidity_mint != spl_token::native_mint::id() {
+        add_reserve_mint(
+            pt,
+            &reserve.liquidity_mint,
+            mint_authority,
+            0,
+            reserve.decimals,
+            true,
+        );
+    }
+
+    add_reserve_mint(
+        pt,
+        &reserve.collateral_mint,
+        &lending_market_authority,
+        state.collateral.mint_total_supply,
+        reserve.decimals,
+        false,
+    );
+
+    add_reserve_token_account(
+        pt,
+        &reserve.liquidity_supply_vault,
+        &reserve.liquidity_mint,
+        &lending_market_authority,
+        state.liquidity.available_amount,
+    );
+
+    add_reserve_token_account(
+        pt,
+        &reserve.liquidity_fee_vault,
+        &reserve.liquidity_mint,
+        &lending_market_authority,
+        0,
+    );
+
+    add_reserve_token_account(
+        pt,
+        &reserve.collateral_supply_vault,
+        &reserve.collateral_mint,
+        &lending_market_authority,
+        state.collateral.mint_total_supply,
+    );
+
+    for farm in [reserve.farm_collateral, reserve.farm_debt] {
+        if farm != Pubkey::default() {
+            pt.add_account_with_file_data(
+                farm,
+                LAMPORTS_PER_SOL,
+                KFARM_PROGRAM_ID,
+                &format!("{}.bin", farm),
+            );
+        }
+    }
+}
+
+fn add_reserve_mint(
+    pt: &mut ProgramTest,
+    mint_address: &Pubkey,
+    mint_authority: &Pubkey,
+    supply: u64,
+    decimals: u8,
+    take_over_authority: bool,
+) {
+    if let Some(filename) = fixture_file(mint_address) {
+        let authority = take_over_authority.then_some(*mint_authority);
+        add_mint(pt, *mint_address, &filename, authority);
+        return;
+    }
+
+    let mint = Mint {
+        mint_authority: COption::Some(*mint_authority),
+        supply,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    let mut data: Vec<u8> = vec![0; Mint::LEN];
+    Mint::pack(mint, &mut data).unwrap();
+
+    pt.add_account(
+        *mint_address,
+        Account {
+            lamports: Rent::default().minimum_balance(Mint::LEN),
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+fn add_reserve_token_account(
+    pt: &mut ProgramTest,
+    address: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) {
+    let rent = Rent::default().minimum_balance(TokenAccount::LEN);
+
+    let data = match fixture_file(address) {
+        Some(filename) => read_account_data(&filename),
+        None => {
+            let is_native = *mint == spl_token::native_mint::id();
+            let token_account = TokenAccount {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                delegate: COption::None,
+                state: AccountState::Initialized,
+                is_native: if is_native {
+                    COption::Some(rent)
+                } else {
+                    COption::None
+                },
+                delegated_amount: 0,
+                close_authority: COption::None,
+            };
+            let mut data: Vec<u8> = vec![0; TokenAccount::LEN];
+            TokenAccount::pack(token_account, &mut data).unwrap();
+            data
+        }
+    };
+
+    // Wrapped SOL accounts must hold their token amount in lamports
+    let token_account = TokenAccount::unpack_from_slice(&data).unwrap();
+    let lamports = match token_account.is_native {
+        COption::Some(rent_exempt_reserve) => token_account.amount + rent_exempt_reserve,
+        COption::None => LAMPORTS_PER_SOL,
+    };
+
+    pt.add_account(
+        *address,
+        Account {
+            lamports,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+pub fn dump_reserve(address: &Pubkey) {
+    let filename = format!("{}.bin", address);
+    let data = read_account_data(&filename);
+    let reserve = Reserve::try_from_slice(&data[8..]).unwrap(); // Skip discriminator !
+
+    print!(
+        "reserve {}\n{}",
+        address,
+        format_fields(&reserve_fields(&reserve))
+    );
+
+    let snapshot = ReserveSnapshot::new(&reserve);
+    println!(
+        "reserve borrow apy = {:.4}, supply apy = {:.4}",
+        snapshot.borrow_apy, snapshot.supply_apy
+    );
+}
+
+pub async fn get_context(registry: &ReserveRegistry) -> Rc<RefCell<ProgramTestContext>> {
+    let mut pt = ProgramTest::default();
+
+    let admin = read_keypair_file("tests/fixtures/admin.json").unwrap();
+
+    // admin takes over the mint authority of every reserve liquidity mint
+    load_kamino_fixtures(&mut pt, registry, &admin.pubkey());
+
+    let context = pt.start_with_context().await;
+
+    Rc::new(RefCell::new(context))
+}
+
+pub async fn get_keypair(file_path: &str) -> Keypair {
+    read_keypair_file(file_path).unwrap()
+}
+
+pub async fn create_payer_from_file(context: &mut ProgramTestContext, file_path: &str) -> Keypair {
+    let keypair = read_keypair_file(file_path).unwrap();
+
+    transfer(context, &keypair.pubkey(), 1_000_000_000).await;
+
+    keypair
+}
+
+pub async fn create_user(context: &mut ProgramTestContext) -> Keypair {
+    let keypair = Keypair::new();
+
+    transfer(context, &keypair.pubkey(), 1_000_000_000).await;
+
+    keypair
+}
+
+pub async fn transfer(context: &mut ProgramTestContext, recipient: &Pubkey, amount: u64) {
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &context.payer.pubkey(),
+            recipient,
+            amount,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.banks_client.get_latest_blockhash().await.unwrap(),
+    );
+
+    context
+        .banks_client
+        .process_transaction_with_preflight(transaction)
+        .await
+        .unwrap();
+}
+
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+pub const DEFAULT_COMPUTE_UNIT_PRICE: u64 = 10_000; // micro lamports, as our production transactions
+const COMPUTE_UNIT_MARGIN_PCT: u64 = 10;
+
+/// ComputeBudget instructions prepended to every transaction, as in our production transactions.
+/// Flash loans count them in the instruction index of their borrow, see `instruction_count`.
+#[derive(Clone, Copy, Debug)]
+pub struct ComputeBudget {
+    /// Compute unit limit, replaced by the simulated consumption when auto-sized
+    pub unit_limit: u32,
+    /// Sizes the limit from a simulation of the transaction, plus a margin
+    pub auto_size: bool,
+    /// Priority fee in micro lamports per compute unit, None sets no price
+    pub unit_price: Option<u64>,
+}
+
+impl Default for ComputeBudget {
+    fn default() -> ComputeBudget {
+        ComputeBudget {
+            unit_limit: MAX_COMPUTE_UNIT_LIMIT,
+            auto_size: false,
+            unit_price: None,
+        }
+    }
+}
+
+impl ComputeBudget {
+    /// Limit sized from a simulation and the priority fee of our production transactions.
+    pub fn auto_sized() -> ComputeBudget {
+        ComputeBudget {
+            unit_limit: MAX_COMPUTE_UNIT_LIMIT,
+            auto_size: true,
+            unit_price: Some(DEFAULT_COMPUTE_UNIT_PRICE),
+        }
+    }
+
+    /// Number of instructions prepended to the transaction.
+    pub fn instruction_count(&self) -> usize {
+        1 + self.unit_price.iter().count()
+    }
+
+    fn compose_ixs(&self, unit_limit: u32) -> Vec<Instruction> {
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(unit_limit)];
+        if let Some(unit_price) = self.unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(unit_price));
+        }
+
+        instructions
+    }
+
+    fn prepend_to(&self, instructions: &[Instruction], unit_limit: u32) -> Vec<Instruction> {
+        let mut budgeted = self.compose_ixs(unit_limit);
+        budgeted.extend_from_slice(instructions);
+
+        budgeted
+    }
+}
+
+pub async fn process_instructions(
+    context: &mut ProgramTestContext,
+    admin: &Keypair,
+    instructions: &[Instruction],
+) -> TxReport {
+    process_instructions_with_budget(context, admin, instructions, ComputeBudget::default()).await
+}
+
+pub async fn process_instructions_with_budget(
+    context: &mut ProgramTestContext,
+    admin: &Keypair,
+    instructions: &[Instruction],
+    budget: ComputeBudget,
+) -> TxReport {
+    let report = execute_instructions(context, admin, instructions, budget, &[])
+        .await
+        .unwrap_or_else(|e| panic!("transaction not processed: {}", e));
+    report.assert_success();
+
+    report
+}
+
+/// Same as `process_instructions`, the report also holds the changes the transaction made to the
+/// given accounts.
+pub async fn process_instructions_with_diff(
+    context: &mut ProgramTestContext,
+    admin: &Keypair,
+    instructions: &[Instruction],
+    watched: &[Pubkey],
+) -> TxReport {
+    let before = AccountSnapshot::take(context, watched).await;
+    let mut report = process_instructions(context, admin, instructions).await;
+    let after = AccountSnapshot::take(context, watched).await;
+    report.state_diff = Some(before.diff(&after));
+
+    report
+}
+
+/// Same as `process_instructions` with a v0 transaction, addresses found in the lookup tables
+/// are passed by index.
+pub async fn process_v0_instructions(
+    context: &mut ProgramTestContext,
+    admin: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> TxReport {
+    let report = execute_instructions(
+        context,
+        admin,
+        instructions,
+        ComputeBudget::default(),
+        lookup_tables,
+    )
+    .await
+    .unwrap_or_else(|e| panic!("transaction not processed: {}", e));
+    report.assert_success();
+
+    report
+}
+
+/// Same as `process_instructions`, returns the error of a failed transaction, or the transport
+/// error if it could not be processed. The slot moves on once processed, failed or not.
+pub async fn try_process_instructions(
+    context: &mut ProgramTestContext,
+    admin: &Keypair,
+    instructions: &[Instruction],
+) -> Result<(), TransportError> {
+    execute_instructions(context, admin, instructions, ComputeBudget::default(), &[])
+        .await?
+        .result
+        .map_err(TransportError::from)
+}
+
+/// Size of the transaction `process_instructions` (or `process_v0_instructions` given lookup
+/// tables) would send, without sending it.
+pub fn check_transaction_size(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> TxSize {
+    let budgeted = ComputeBudget::default().prepend_to(instructions, MAX_COMPUTE_UNIT_LIMIT);
+
+    let message = compose_message(payer, &budgeted, lookup_tables, Hash::default());
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    };
+
+    TxSize::new(&transaction, &budgeted, lookup_tables)
+}
+
+/// A legacy message without lookup tables, a v0 one otherwise.
+pub fn compose_message(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> VersionedMessage {
+    if lookup_tables.is_empty() {
+        VersionedMessage::Legacy(Message::new_with_blockhash(
+            instructions,
+            Some(payer),
+            &blockhash,
+        ))
+    } else {
+        VersionedMessage::V0(
+            v0::Message::try_compile(payer, instructions, lookup_tables, blockhash).unwrap(),
+        )
+    }
+}
+
+/// Processes the instructions in one transaction whatever the outcome, then moves to the next
+/// slot. Without lookup tables the transaction is a legacy one. Errs only if the transaction
+/// could not be processed at all, e.g. on an RPC or timeout error.
+pub async fn execute_instructions(
+    context: &mut ProgramTestContext,
+    admin: &Keypair,
+    instructions: &[Instruction],
+    budget: ComputeBudget,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<TxReport, TransportError> {
+    let budgeted = |unit_limit: u32| budget.prepend_to(instructions, unit_limit);
+    let sign = |unit_limit: u32| {
+        let message = compose_message(
+            &admin.pubkey(),
+            &budgeted(unit_limit),
+            lookup_tables,
+            context.last_blockhash,
+        );
+
+        VersionedTransaction::try_new(message, &[admin]).unwrap()
+    };
+
+    TxSize::new(
+        &sign(MAX_COMPUTE_UNIT_LIMIT),
+        &budgeted(MAX_COMPUTE_UNIT_LIMIT),
+        lookup_tables,
+    )
+    .assert_fits();
+
+    // A failed simulation keeps the given limit, the transaction then reports the failure itself
+    let unit_limit = if budget.auto_size {
+        context
+            .banks_client
+            .simulate_transaction(sign(MAX_COMPUTE_UNIT_LIMIT))
+            .await
+            .ok()
+            .filter(|simulated| matches!(simulated.result, Some(Ok(()))))
+            .and_then(|simulated| simulated.simulation_details)
+            .map(|details| {
+                let units = details.units_consumed * (100 + COMPUTE_UNIT_MARGIN_PCT) / 100;
+                units.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+            })
+            .unwrap_or(budget.unit_limit)
+    } else {
+        budget.unit_limit
+    };
+
+    let transaction = sign(unit_limit);
+    let report = |result, logs, compute_units_consumed, return_data| {
+        let mut report = TxReport::new(
+            &transaction,
+            lookup_tables,
+            result,
+            logs,
+            compute_units_consumed,
+            return_data,
+        );
+        report.compute_unit_limit = unit_limit;
+        report
+    };
+
+    let report = match context
+        .banks_client
+        .process_transaction_with_metadata(transaction.clone())
+        .await
+    {
+        Ok(processed) => match processed.metadata {
+            Some(metadata) => report(
+                processed.result,
+                metadata.log_messages,
+                metadata.compute_units_consumed,
+                metadata.return_data,
+            ),
+            None => report(processed.result, vec![], 0, None),
+        },
+        Err(BanksClientError::TransactionError(err))
+        | Err(BanksClientError::SimulationError { err, .. }) => report(Err(err), vec![], 0, None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let clock = get_sysvar_clock(&mut context.banks_client).await;
+    context.warp_to_slot(clock.slot + 1).unwrap();
+
+    Ok(report)
+}
+
+pub async fn create_token_account(
+    context: &mut ProgramTestContext,
+    payer: &Keypair,
+    account: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    extra_lamports: u64,
+) -> Result<(), TransportError> {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let account_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                account_rent + extra_lamports,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        Some(&payer.pubkey()),
+        &[payer, account],
+        context.banks_client.get_latest_blockhash().await.unwrap(),
+    );
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.into())
+}
+
+pub async fn create_account(
+    context: &mut ProgramTestContext,
+    payer: &Keypair,
+    account: &Keypair,
+    size: usize,
+    owner: &Pubkey,
+) -> Result<(), TransportError> {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let account_rent = rent.minimum_balance(size);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &account.pubkey(),
+            account_rent,
+            size as u64,
+            owner,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, account], context.last_blockhash);
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.into())
+}
+
+pub async fn create_associated_token_account(
+    context: &mut ProgramTestContext,
+    payer: &Keypair,
+    user: &Pubkey,
+    mint: &Pubkey,
+) {
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            spl_associated_token_account::instruction::create_associated_token_account(
+                &payer.pubkey(),
+                user,
+                mint,
+                &spl_token::id(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[payer],
+        context.banks_client.get_latest_blockhash().await.unwrap(),
+    );
+    // transaction.sign(&[payer], context.last_blockhash);
+
+    context
+        .banks_client
+        .process_transaction_with_preflight(transaction)
+        .await
+        .unwrap();
+}
+
+pub async fn get_associated_token_address(user: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(user, mint)
+}
+
+pub async fn get_or_create_associated_token_address(
+    context: &mut ProgramTestContext,
+    payer: &Keypair,
+    user: &Pubkey,
+    mint: &Pubkey,
+) -> Pubkey {
+    let associate_account = spl_associated_token_account::get_associated_token_address(user, mint);
+    match context.banks_client.get_account(associate_account).await {
+        Ok(None) => create_associated_token_account(context, payer, user, mint).await,
+        Ok(Some(_)) => {} // Do nothing if already exists
+        Err(_) => panic!("Got error when getting associated account"),
+    }
+
+    associate_account
+}
+
+pub async fn spl_token_mint(
+    context: &mut ProgramTestContext,
+    payer: &Keypair,
+    mint: &Pubkey,
+    account: &Pubkey,
+    mint_authority: &Keypair,
+    amount: u64,
+) -> Result<(), TransportError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            account,
+            &mint_authority.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[payer, mint_authority],
+        context.last_blockhash,
+    );
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.into())
+}
+
+pub async fn get_sysvar_clock(banks_client: &mut BanksClient) -> Clock {
+    let clock_account = banks_client
+        .get_account(sysvar::clock::id())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let clock: Clock = bincode::deserialize(&clock_account.data).unwrap();
+
+    clock
+}
+
+pub async fn get_account(banks_client: &mut BanksClient, address: Pubkey) -> Option<Account> {
+    banks_client.get_account(address).await.unwrap()
+}
+
+pub async fn get_token_balance(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    let token_account = banks_client.get_account(address).await.unwrap().unwrap();
+    let account_info: spl_token::state::Account =
+        spl_token::state::Account::unpack_from_slice(token_account.data.as_slice()).unwrap();
+    account_info.amount
+}
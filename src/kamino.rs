@@ -0,0 +1,811 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::klend_instructions as klend_ix;
+use crate::reserve_registry::ReserveInfo;
+use solana_program::instruction::AccountMeta;
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::system_program;
+use solana_sdk::{instruction::Instruction, sysvar};
+use spl_token;
+
+pub const KLEND_PROGRAM_ID: Pubkey = pubkey!("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD");
+pub const KFARM_PROGRAM_ID: Pubkey = pubkey!("FarmsPZpWu9i7Kky8tPN37rs2TpmMrAZrC7S7vJa91Hr");
+pub const KLEND_SCOPE_PRICES_PROGRAM_ID: Pubkey =
+    pubkey!("HFn8GnPADiny6XqUoWE8uRPPxb29ikn4yTuPa9MF2fWJ");
+
+pub const MAIN_MARKET: Pubkey = pubkey!("7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF");
+pub const ALTCOINS_MARKET: Pubkey = pubkey!("ByYiZxp8QrdN9qbdtaAiePN8AAr3qvTPppNJDpf5DVJ5");
+pub const KAMINO_SCOPE_PRICES: Pubkey = pubkey!("3NJYftD5sjVfxSnUdZ1wVML8f3aC6mp1CXCL6L7TnU8C");
+
+pub const JITOSOL_MINT: Pubkey = pubkey!("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn"); // Mint
+
+// Reserve states, all related accounts are read from the reserve itself (see ReserveRegistry)
+pub const RESERVE_JITOSOL_STATE: Pubkey = pubkey!("EVbyPKrHG6WBfm4dLxLMJpUDY43cCAcHSpV3KYjKsktW");
+pub const RESERVE_SOL_STATE: Pubkey = pubkey!("d4A2prbA2whesmvHaL88BH6Ewn5N4bTSU2Ze8P6Bc4Q");
+pub const RESERVE_USDC_STATE: Pubkey = pubkey!("D6q6wuQSrifJKZYpR1M8R4YawnLDtDsMmWM1NbBmgJ59");
+pub const RESERVE_JTO_STATE: Pubkey = pubkey!("8PYYKF4ZvteefFBmtb9SMHmhZKnDWQH86z59mPZBfhHu"); // Altcoins market
+
+pub const KAMINO_MARKETS: [Pubkey; 2] = [MAIN_MARKET, ALTCOINS_MARKET];
+
+pub const KAMINO_RESERVES: [Pubkey; 4] = [
+    RESERVE_JITOSOL_STATE,
+    RESERVE_SOL_STATE,
+    RESERVE_USDC_STATE,
+    RESERVE_JTO_STATE,
+];
+
+/// Reserve farms without a fixture file, detached from their reserve when loading the fixtures:
+/// USDC collateral farm, JTO collateral farm.
+pub const DETACHED_FARMS: [Pubkey; 2] = [
+    pubkey!("JAvnB9AKtgPsTEoKmn24Bq64UMoYcrtWtq42HHBdsPkh"),
+    pubkey!("9ZL7vSx7DgUKKVUCVxkchFQupaSZCPLFLNgrTavd6W3a"),
+];
+
+/// Typed selector of a registered reserve, shared by every user flow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Asset {
+    JitoSol,
+    Sol,
+    Usdc,
+    Jto,
+    Reserve(Pubkey), // Any other reserve of the ReserveRegistry
+}
+
+impl Asset {
+    pub fn reserve(&self) -> Pubkey {
+        match self {
+            Asset::JitoSol => RESERVE_JITOSOL_STATE,
+            Asset::Sol => RESERVE_SOL_STATE,
+            Asset::Usdc => RESERVE_USDC_STATE,
+            Asset::Jto => RESERVE_JTO_STATE,
+            Asset::Reserve(reserve) => *reserve,
+        }
+    }
+}
+
+/// Which side of an obligation is staked in the reserve farm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FarmMode {
+    Collateral = 0,
+    Debt = 1,
+}
+
+pub const EXAMPLE_OBLIGATION: Pubkey = pubkey!("4w1qUuYbXpwYkqWzuovJFvP3TRt9EgEzYQv7kYY1nM3J");
+
+pub fn lending_market_authority(market: &Pubkey) -> Pubkey {
+    let (authority, _) =
+        Pubkey::find_program_address(&[b"lma", &market.to_bytes()], &KLEND_PROGRAM_ID);
+
+    authority
+}
+
+pub fn user_metadata(owner: &Pubkey) -> Pubkey {
+    let (user_metadata, _) =
+        Pubkey::find_program_address(&[b"user_meta", &owner.to_bytes()], &KLEND_PROGRAM_ID);
+
+    user_metadata
+}
+
+pub fn referrer_state(referrer: &Pubkey) -> Pubkey {
+    let (referrer_state, _) =
+        Pubkey::find_program_address(&[b"ref_state", &referrer.to_bytes()], &KLEND_PROGRAM_ID);
+
+    referrer_state
+}
+
+pub fn referrer_short_url(short_url: &str) -> Pubkey {
+    let (referrer_short_url, _) =
+        Pubkey::find_program_address(&[b"short_url", short_url.as_bytes()], &KLEND_PROGRAM_ID);
+
+    referrer_short_url
+}
+
+/// The kfarm user state staking the obligation side of a reserve farm.
+pub fn obligation_farm_user_state(reserve_farm_state: &Pubkey, obligation: &Pubkey) -> Pubkey {
+    let (obligation_farm, _) = Pubkey::find_program_address(
+        &[
+            b"user",
+            &reserve_farm_state.to_bytes(),
+            &obligation.to_bytes(),
+        ],
+        &KFARM_PROGRAM_ID,
+    );
+
+    obligation_farm
+}
+
+/// Where the referral fees a referrer earns on a reserve accumulate.
+pub fn referrer_token_state(referrer: &Pubkey, reserve: &Pubkey) -> Pubkey {
+    let (referrer_token_state, _) = Pubkey::find_program_address(
+        &[b"referrer_acc", &referrer.to_bytes(), &reserve.to_bytes()],
+        &KLEND_PROGRAM_ID,
+    );
+
+    referrer_token_state
+}
+
+pub fn compose_klend_init_user_metadata_ix(
+    owner: &Pubkey,
+    fee_payer: &Pubkey,
+    user_metadata: &Pubkey,
+    user_lookup_table: &Pubkey,
+) -> Instruction {
+    klend_ix::accounts::InitUserMetadata {
+        owner: *owner,
+        fee_payer: *fee_payer,
+        user_metadata: *user_metadata,
+        referrer_user_metadata: None,
+        rent: sysvar::rent::id(),
+        system_program: system_program::ID,
+    }
+    .instruction(&klend_ix::instruction::InitUserMetadata {
+        user_lookup_table: *user_lookup_table,
+    })
+}
+
+pub fn compose_klend_init_obligation_ix(
+    owner: &Pubkey,
+    fee_payer: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    seed1_account: &Pubkey,
+    seed2_account: &Pubkey,
+    user_metadata: &Pubkey,
+    tag: u8,
+    id: u8,
+) -> Instruction {
+    klend_ix::accounts::InitObligation {
+        obligation_owner: *owner,
+        fee_payer: *fee_payer,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        seed1_account: *seed1_account,
+        seed2_account: *seed2_account,
+        owner_user_metadata: *user_metadata,
+        rent: sysvar::rent::id(),
+        system_program: system_program::ID,
+    }
+    .instruction(&klend_ix::instruction::InitObligation {
+        args: klend_ix::types::InitObligationArgs { tag, id },
+    })
+}
+
+/// Passes the oracles configured in the reserve, unset ones are left out.
+pub fn compose_klend_refresh_reserve_ix(reserve: &ReserveInfo) -> Instruction {
+    let oracle = |address: Pubkey| Some(address).filter(|a| *a != Pubkey::default());
+
+    klend_ix::accounts::RefreshReserve {
+        reserve: reserve.address,
+        lending_market: reserve.lending_market,
+        pyth_oracle: oracle(reserve.pyth_price),
+        switchboard_price_oracle: oracle(reserve.switchboard_price_aggregator),
+        switchboard_twap_oracle: oracle(reserve.switchboard_twap_aggregator),
+        scope_prices: oracle(reserve.scope_prices),
+    }
+    .instruction(&klend_ix::instruction::RefreshReserve {})
+}
+
+pub fn compose_klend_refresh_obligation_ix(
+    obligation: &Pubkey,
+    market: &Pubkey,
+    reserves: &[Pubkey],
+) -> Instruction {
+    let mut instruction = klend_ix::accounts::RefreshObligation {
+        lending_market: *market,
+        obligation: *obligation,
+    }
+    .instruction(&klend_ix::instruction::RefreshObligation {});
+
+    for reserve in reserves {
+        instruction.accounts.push(AccountMeta::new(*reserve, false));
+    }
+
+    instruction
+}
+
+pub fn compose_klend_deposit_reserve_liquidity_ix(
+    owner: &Pubkey,
+    reserve: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_liquidity_supply: &Pubkey,
+    reserve_collateral_mint: &Pubkey,
+    user_source_liquidity: &Pubkey,
+    user_destination_collateral: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::DepositReserveLiquidity {
+        owner: *owner,
+        reserve: *reserve,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_liquidity_supply: *reserve_liquidity_supply,
+        reserve_collateral_mint: *reserve_collateral_mint,
+        user_source_liquidity: *user_source_liquidity,
+        user_destination_collateral: *user_destination_collateral,
+        collateral_token_program: spl_token::id(),
+        liquidity_token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::DepositReserveLiquidity { liquidity_amount })
+}
+
+pub fn compose_klend_redeem_reserve_collateral_ix(
+    owner: &Pubkey,
+    reserve: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_liquidity_supply: &Pubkey,
+    reserve_collateral_mint: &Pubkey,
+    user_source_collateral: &Pubkey,
+    user_destination_liquidity: &Pubkey,
+    collateral_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::RedeemReserveCollateral {
+        owner: *owner,
+        lending_market: *lending_market,
+        reserve: *reserve,
+        lending_market_authority: *lending_market_authority,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_collateral_mint: *reserve_collateral_mint,
+        reserve_liquidity_supply: *reserve_liquidity_supply,
+        user_source_collateral: *user_source_collateral,
+        user_destination_liquidity: *user_destination_liquidity,
+        collateral_token_program: spl_token::id(),
+        liquidity_token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::RedeemReserveCollateral { collateral_amount })
+}
+
+pub fn compose_klend_deposit_obligation_collateral_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    deposit_reserve: &Pubkey,
+    reserve_destination_collateral: &Pubkey,
+    user_source_collateral: &Pubkey,
+    collateral_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::DepositObligationCollateral {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        deposit_reserve: *deposit_reserve,
+        reserve_destination_collateral: *reserve_destination_collateral,
+        user_source_collateral: *user_source_collateral,
+        token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::DepositObligationCollateral { collateral_amount })
+}
+
+pub fn compose_klend_withdraw_obligation_collateral_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    reserve_source_collateral: &Pubkey,
+    user_destination_collateral: &Pubkey,
+    collateral_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::WithdrawObligationCollateral {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        withdraw_reserve: *withdraw_reserve,
+        reserve_source_collateral: *reserve_source_collateral,
+        user_destination_collateral: *user_destination_collateral,
+        token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::WithdrawObligationCollateral { collateral_amount })
+}
+
+pub fn compose_klend_borrow_obligation_liquidity_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    borrow_reserve: &Pubkey,
+    borrow_reserve_liquidity_mint: &Pubkey,
+    reserve_source_liquidity: &Pubkey,
+    borrow_reserve_liquidity_fee_receiver: &Pubkey,
+    user_destination_liquidity: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::BorrowObligationLiquidity {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        borrow_reserve: *borrow_reserve,
+        borrow_reserve_liquidity_mint: *borrow_reserve_liquidity_mint,
+        reserve_source_liquidity: *reserve_source_liquidity,
+        borrow_reserve_liquidity_fee_receiver: *borrow_reserve_liquidity_fee_receiver,
+        user_destination_liquidity: *user_destination_liquidity,
+        referrer_token_state: None,
+        token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::BorrowObligationLiquidity { liquidity_amount })
+}
+
+pub fn compose_klend_repay_obligation_liquidity_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    repay_reserve: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_destination_liquidity: &Pubkey,
+    user_source_liquidity: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::RepayObligationLiquidity {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        repay_reserve: *repay_reserve,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_destination_liquidity: *reserve_destination_liquidity,
+        user_source_liquidity: *user_source_liquidity,
+        token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::RepayObligationLiquidity { liquidity_amount })
+}
+
+pub fn compose_klend_liquidate_obligation_and_redeem_reserve_collateral_ix(
+    liquidator: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    repay_reserve: &Pubkey,
+    repay_reserve_liquidity_mint: &Pubkey,
+    repay_reserve_liquidity_supply: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    withdraw_reserve_liquidity_mint: &Pubkey,
+    withdraw_reserve_collateral_mint: &Pubkey,
+    withdraw_reserve_collateral_supply: &Pubkey,
+    withdraw_reserve_liquidity_supply: &Pubkey,
+    withdraw_reserve_liquidity_fee_receiver: &Pubkey,
+    user_source_liquidity: &Pubkey,
+    user_destination_collateral: &Pubkey,
+    user_destination_liquidity: &Pubkey,
+    liquidity_amount: u64,
+    min_acceptable_received_liquidity_amount: u64,
+    max_allowed_ltv_override_percent: u64, // Only honored by the staging program
+) -> Instruction {
+    klend_ix::accounts::LiquidateObligationAndRedeemReserveCollateral {
+        liquidator: *liquidator,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        repay_reserve: *repay_reserve,
+        repay_reserve_liquidity_mint: *repay_reserve_liquidity_mint,
+        repay_reserve_liquidity_supply: *repay_reserve_liquidity_supply,
+        withdraw_reserve: *withdraw_reserve,
+        withdraw_reserve_liquidity_mint: *withdraw_reserve_liquidity_mint,
+        withdraw_reserve_collateral_mint: *withdraw_reserve_collateral_mint,
+        withdraw_reserve_collateral_supply: *withdraw_reserve_collateral_supply,
+        withdraw_reserve_liquidity_supply: *withdraw_reserve_liquidity_supply,
+        withdraw_reserve_liquidity_fee_receiver: *withdraw_reserve_liquidity_fee_receiver,
+        user_source_liquidity: *user_source_liquidity,
+        user_destination_collateral: *user_destination_collateral,
+        user_destination_liquidity: *user_destination_liquidity,
+        collateral_token_program: spl_token::id(),
+        repay_liquidity_token_program: spl_token::id(),
+        withdraw_liquidity_token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(
+        &klend_ix::instruction::LiquidateObligationAndRedeemReserveCollateral {
+            liquidity_amount,
+            min_acceptable_received_liquidity_amount,
+            max_allowed_ltv_override_percent,
+        },
+    )
+}
+
+pub const MOCK_SWAP_RATE: u64 = 12000; // 1.2 sol per jitosol
+
+pub fn mock_swap_output(amount: u64, rate: u64) -> u64 {
+    amount * 10000 / rate
+}
+
+/// Swap between any two reserve assets for tests: the source tokens are moved to `source_sink`,
+/// the destination tokens are minted, or transferred from `destination_funding` for wsol.
+pub fn compose_mock_swap_ix(
+    owner: &Pubkey, // must be mint authority of the destination mint
+    source_account: &Pubkey,
+    source_sink: &Pubkey,
+    source_amount: u64,
+    destination_mint: &Pubkey,
+    destination_account: &Pubkey,
+    destination_funding: Option<&Pubkey>,
+    destination_amount: u64,
+) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = vec![];
+
+    instructions.push(
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            source_account,
+            source_sink,
+            owner,
+            &[owner],
+            source_amount,
+        )
+        .unwrap(),
+    );
+
+    if *destination_mint == spl_token::native_mint::id() {
+        instructions.push(
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                destination_funding.expect("wsol destination needs a funding account"),
+                destination_account,
+                owner,
+                &[owner],
+                destination_amount,
+            )
+            .unwrap(),
+        );
+    } else {
+        instructions.push(
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                destination_mint,
+                destination_account,
+                owner,
+                &[],
+                destination_amount,
+            )
+            .unwrap(),
+        );
+    }
+
+    instructions
+}
+
+pub fn compose_mock_swap_sol_to_jitosol_ix(
+    owner: &Pubkey, // must be mint authority of jitosol for ease of test
+    owner_wsol_account: &Pubkey,
+    destination_account: &Pubkey,
+    owner_jitosol_account: &Pubkey,
+    amount: u64,
+    rate: u64,
+) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = vec![];
+
+    instructions.push(
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            owner_wsol_account,
+            destination_account,
+            owner,
+            &[owner],
+            amount,
+        )
+        .unwrap(),
+    );
+
+    let jitosol_amount = mock_swap_output(amount, rate);
+    instructions.push(
+        spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &JITOSOL_MINT,
+            owner_jitosol_account,
+            owner,
+            &[],
+            jitosol_amount,
+        )
+        .unwrap(),
+    );
+
+    instructions
+}
+
+pub fn compose_mock_swap_jitosol_to_sol_ix(
+    owner: &Pubkey, // must be mint authority of jitosol for ease of test
+    owner_wsol_account: &Pubkey,
+    source_wsol_account: &Pubkey,
+    owner_jitosol_account: &Pubkey,
+    amount: u64,
+    rate: u64,
+) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = vec![];
+
+    instructions.push(
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            source_wsol_account,
+            owner_wsol_account,
+            owner,
+            &[owner],
+            amount,
+        )
+        .unwrap(),
+    );
+
+    let jitosol_amount = mock_swap_output(amount, rate);
+    instructions.push(
+        spl_token::instruction::burn(
+            &spl_token::id(),
+            owner_jitosol_account,
+            &JITOSOL_MINT,
+            owner,
+            &[],
+            jitosol_amount,
+        )
+        .unwrap(),
+    );
+
+    instructions
+}
+
+pub fn compose_klend_flash_borrow_ix(
+    user: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    reserve: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_source_liquidity: &Pubkey,
+    user_destination_liquidity: &Pubkey,
+    reserve_liquidity_fee_receiver: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::FlashBorrowReserveLiquidity {
+        user_transfer_authority: *user,
+        lending_market_authority: *lending_market_authority,
+        lending_market: *lending_market,
+        reserve: *reserve,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_source_liquidity: *reserve_source_liquidity,
+        user_destination_liquidity: *user_destination_liquidity,
+        reserve_liquidity_fee_receiver: *reserve_liquidity_fee_receiver,
+        referrer_token_state: None,
+        referrer_account: None,
+        sysvar_info: sysvar::instructions::ID,
+        token_program: spl_token::id(),
+    }
+    .instruction(&klend_ix::instruction::FlashBorrowReserveLiquidity { liquidity_amount })
+}
+
+pub fn compose_klend_flash_repay_ix(
+    user: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    reserve: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_destination_liquidity: &Pubkey,
+    user_source_liquidity: &Pubkey,
+    reserve_liquidity_fee_receiver: &Pubkey,
+    liquidity_amount: u64,
+    borrow_instruction_index: u8,
+) -> Instruction {
+    klend_ix::accounts::FlashRepayReserveLiquidity {
+        user_transfer_authority: *user,
+        lending_market_authority: *lending_market_authority,
+        lending_market: *lending_market,
+        reserve: *reserve,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_destination_liquidity: *reserve_destination_liquidity,
+        user_source_liquidity: *user_source_liquidity,
+        reserve_liquidity_fee_receiver: *reserve_liquidity_fee_receiver,
+        referrer_token_state: None,
+        referrer_account: None,
+        sysvar_info: sysvar::instructions::ID,
+        token_program: spl_token::id(),
+    }
+    .instruction(&klend_ix::instruction::FlashRepayReserveLiquidity {
+        liquidity_amount,
+        borrow_instruction_index,
+    })
+}
+
+pub fn compose_klend_init_obligation_farms_for_reserve_ix(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market_authority: &Pubkey,
+    reserve: &Pubkey,
+    reserve_farm_state: &Pubkey,
+    obligation_farm: &Pubkey,
+    lending_market: &Pubkey,
+    mode: u8,
+) -> Instruction {
+    klend_ix::accounts::InitObligationFarmsForReserve {
+        payer: *payer,
+        owner: *owner,
+        obligation: *obligation,
+        lending_market_authority: *lending_market_authority,
+        reserve: *reserve,
+        reserve_farm_state: *reserve_farm_state,
+        obligation_farm: *obligation_farm,
+        lending_market: *lending_market,
+        farms_program: KFARM_PROGRAM_ID,
+        rent: sysvar::rent::id(),
+        system_program: system_program::ID,
+    }
+    .instruction(&klend_ix::instruction::InitObligationFarmsForReserve { mode })
+}
+
+pub fn compose_klend_deposit_reserve_liquidity_and_obligation_collateral_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    reserve: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_liquidity_supply: &Pubkey,
+    reserve_collateral_mint: &Pubkey,
+    reserve_destination_deposit_collateral: &Pubkey,
+    user_source_liquidity: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::DepositReserveLiquidityAndObligationCollateral {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        reserve: *reserve,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_liquidity_supply: *reserve_liquidity_supply,
+        reserve_collateral_mint: *reserve_collateral_mint,
+        reserve_destination_deposit_collateral: *reserve_destination_deposit_collateral,
+        user_source_liquidity: *user_source_liquidity,
+        placeholder_user_destination_collateral: None,
+        collateral_token_program: spl_token::id(),
+        liquidity_token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(
+        &klend_ix::instruction::DepositReserveLiquidityAndObligationCollateral { liquidity_amount },
+    )
+}
+
+pub fn compose_klend_withdraw_obligation_collateral_and_redeem_reserve_collateral_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_source_collateral: &Pubkey,
+    reserve_collateral_mint: &Pubkey,
+    reserve_liquidity_supply: &Pubkey,
+    user_destination_liquidity: &Pubkey,
+    collateral_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::WithdrawObligationCollateralAndRedeemReserveCollateral {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        withdraw_reserve: *withdraw_reserve,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_source_collateral: *reserve_source_collateral,
+        reserve_collateral_mint: *reserve_collateral_mint,
+        reserve_liquidity_supply: *reserve_liquidity_supply,
+        user_destination_liquidity: *user_destination_liquidity,
+        placeholder_user_destination_collateral: None,
+        collateral_token_program: spl_token::id(),
+        liquidity_token_program: spl_token::id(),
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(
+        &klend_ix::instruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
+            collateral_amount,
+        },
+    )
+}
+
+/// The reserves of the obligation, deposits first then borrows, follow as remaining accounts.
+pub fn compose_klend_request_elevation_group_ix(
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    obligation_reserves: &[Pubkey],
+    elevation_group: u8,
+) -> Instruction {
+    let mut instruction = klend_ix::accounts::RequestElevationGroup {
+        owner: *owner,
+        obligation: *obligation,
+        lending_market: *lending_market,
+    }
+    .instruction(&klend_ix::instruction::RequestElevationGroup { elevation_group });
+
+    for reserve in obligation_reserves {
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(*reserve, false));
+    }
+
+    instruction
+}
+
+pub fn compose_klend_init_referrer_token_state_ix(
+    payer: &Pubkey,
+    lending_market: &Pubkey,
+    reserve: &Pubkey,
+    referrer: &Pubkey,
+) -> Instruction {
+    klend_ix::accounts::InitReferrerTokenState {
+        payer: *payer,
+        lending_market: *lending_market,
+        reserve: *reserve,
+        referrer_token_state: referrer_token_state(referrer, reserve),
+        rent: sysvar::rent::id(),
+        system_program: system_program::ID,
+    }
+    .instruction(&klend_ix::instruction::InitReferrerTokenState {
+        referrer: *referrer,
+    })
+}
+
+/// The referrer needs a user metadata, short urls are ascii alphanumeric (and '_', '-').
+pub fn compose_klend_init_referrer_state_and_short_url_ix(
+    referrer: &Pubkey,
+    short_url: &str,
+) -> Instruction {
+    klend_ix::accounts::InitReferrerStateAndShortUrl {
+        referrer: *referrer,
+        referrer_state: referrer_state(referrer),
+        referrer_short_url: referrer_short_url(short_url),
+        referrer_user_metadata: user_metadata(referrer),
+        rent: sysvar::rent::id(),
+        system_program: system_program::ID,
+    }
+    .instruction(&klend_ix::instruction::InitReferrerStateAndShortUrl {
+        short_url: short_url.to_string(),
+    })
+}
+
+pub fn compose_klend_withdraw_referrer_fees_ix(
+    referrer: &Pubkey,
+    reserve: &Pubkey,
+    reserve_liquidity_mint: &Pubkey,
+    reserve_supply_liquidity: &Pubkey,
+    referrer_token_account: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+) -> Instruction {
+    klend_ix::accounts::WithdrawReferrerFees {
+        referrer: *referrer,
+        referrer_token_state: referrer_token_state(referrer, reserve),
+        reserve: *reserve,
+        reserve_liquidity_mint: *reserve_liquidity_mint,
+        reserve_supply_liquidity: *reserve_supply_liquidity,
+        referrer_token_account: *referrer_token_account,
+        lending_market: *lending_market,
+        lending_market_authority: *lending_market_authority,
+        token_program: spl_token::id(),
+    }
+    .instruction(&klend_ix::instruction::WithdrawReferrerFees {})
+}
+
+/// Only the risk council of the market may socialize the debt of an obligation without
+/// collateral left.
+pub fn compose_klend_socialize_loss_ix(
+    risk_council: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    reserve: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    klend_ix::accounts::SocializeLoss {
+        risk_council: *risk_council,
+        obligation: *obligation,
+        lending_market: *lending_market,
+        reserve: *reserve,
+        instruction_sysvar_account: sysvar::instructions::ID,
+    }
+    .instruction(&klend_ix::instruction::SocializeLoss { liquidity_amount })
+}
@@ -0,0 +1,2 @@
+// kfarm instruction builders generated by build.rs from idl/kfarm.json
+include!(concat!(env!("OUT_DIR"), "/kfarm_instructions.rs"));
@@ -0,0 +1,85 @@
+//! kfarm account layouts, kfarm has no published crate. Zero copy accounts, decoded with borsh
+//! as their fields need no alignment padding.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct TokenInfo {
+    pub mint: Pubkey,
+    pub decimals: u64,
+    pub token_program: Pubkey,
+    pub padding: [u64; 6],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct RewardPerTimeUnitPoint {
+    pub ts_start: u64,
+    pub reward_per_time_unit: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct RewardScheduleCurve {
+    pub points: [RewardPerTimeUnitPoint; 20],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct RewardInfo {
+    pub token: TokenInfo,
+    pub rewards_vault: Pubkey,
+    pub rewards_available: u64,
+    pub reward_schedule_curve: RewardScheduleCurve,
+    pub min_claim_duration_seconds: u64,
+    pub last_issuance_ts: u64,
+    pub rewards_issued_unclaimed: u64,
+    pub rewards_issued_cumulative: u64,
+    pub reward_per_share_scaled: u128,
+    pub placeholder0: u64,
+    pub reward_type: u8,
+    pub rewards_per_second_decimals: u8,
+    pub padding0: [u8; 6],
+    pub padding1: [u64; 20],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct FarmState {
+    pub farm_admin: Pubkey,
+    pub global_config: Pubkey,
+    pub token: TokenInfo,
+    pub reward_infos: [RewardInfo; 10],
+    pub num_reward_tokens: u64,
+    pub num_users: u64,
+    pub total_staked_amount: u64,
+    pub farm_vault: Pubkey,
+    pub farm_vaults_authority: Pubkey,
+    pub farm_vault_authority_bump: u64,
+    /// The lending market authority for reserve farms, stake is delegated to it
+    pub delegate_authority: Pubkey,
+    pub time_unit: u8,
+    pub is_farm_frozen: u8,
+    pub is_farm_delegated: u8,
+    pub padding0: [u8; 5],
+    pub withdraw_authority: Pubkey,
+    pub deposit_warmup_period: u32,
+    pub withdrawal_cooldown_period: u32,
+    pub total_active_stake_scaled: u128,
+    pub total_pending_stake_scaled: u128,
+    pub total_pending_amount: u64,
+    pub slashed_amount_current: u64,
+    pub slashed_amount_cumulative: u64,
+    pub slashed_amount_spill_address: Pubkey,
+    pub locking_mode: u64,
+    pub locking_start_timestamp: u64,
+    pub locking_duration: u64,
+    pub locking_early_withdrawal_penalty_bps: u64,
+    pub deposit_cap_amount: u64,
+    pub scope_prices: Pubkey,
+    pub scope_oracle_price_id: u64,
+    pub scope_oracle_max_age: u64,
+    pub pending_farm_admin: Pubkey,
+    pub strategy_id: Pubkey,
+    pub delegated_rps_admin: Pubkey,
+    pub vault_id: Pubkey,
+    pub second_delegated_authority: Pubkey,
+    pub padding: [u64; 74],
+}
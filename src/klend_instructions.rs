@@ -0,0 +1,3 @@
+// klend instruction builders generated by build.rs from idl/kamino_lending.json, update the IDL
+// rather than the builders when the program changes.
+include!(concat!(env!("OUT_DIR"), "/klend_instructions.rs"));
@@ -0,0 +1,194 @@
+use fixed::types::U68F60 as Fraction;
+use klend::state::{BigFractionBytes, ObligationLiquidity, Reserve};
+
+// Mirrors klend's on-chain math so that tests can predict amounts before a transaction runs.
+
+pub const SLOTS_PER_YEAR: u64 = 63072000; // 2 slots per second
+
+pub fn fraction_from_bps(bps: u64) -> Fraction {
+    Fraction::from_num(bps) / 10_000
+}
+
+pub fn fraction_from_percent(percent: u64) -> Fraction {
+    Fraction::from_num(percent) / 100
+}
+
+pub fn total_supply(reserve: &Reserve) -> Fraction {
+    Fraction::from(reserve.liquidity.available_amount)
+        + Fraction::from_bits(reserve.liquidity.borrowed_amount_sf)
+        - Fraction::from_bits(reserve.liquidity.accumulated_protocol_fees_sf)
+        - Fraction::from_bits(reserve.liquidity.accumulated_referrer_fees_sf)
+        - Fraction::from_bits(reserve.liquidity.pending_referrer_fees_sf)
+}
+
+pub fn utilization_rate(reserve: &Reserve) -> Fraction {
+    let total_supply = total_supply(reserve);
+    if total_supply == Fraction::ZERO {
+        return Fraction::ZERO;
+    }
+
+    Fraction::from_bits(reserve.liquidity.borrowed_amount_sf) / total_supply
+}
+
+pub fn current_borrow_rate(reserve: &Reserve) -> Fraction {
+    borrow_rate_at(reserve, utilization_rate(reserve))
+}
+
+/// Linear interpolation on the reserve borrow rate curve.
+pub fn borrow_rate_at(reserve: &Reserve, utilization_rate: Fraction) -> Fraction {
+    let utilization_rate = utilization_rate.min(Fraction::ONE);
+    let utilization_rate_bps: u32 = (utilization_rate * 10_000).round().to_num();
+    let points = &reserve.config.borrow_rate_curve.points;
+
+    let (start, end) = points
+        .windows(2)
+        .map(|segment| (&segment[0], &segment[1]))
+        .find(|(start, end)| {
+            utilization_rate_bps >= start.utilization_rate_bps
+                && utilization_rate_bps <= end.utilization_rate_bps
+        })
+        .expect("invalid borrow rate curve");
+
+    if utilization_rate_bps == start.utilization_rate_bps {
+        return fraction_from_bps(start.borrow_rate_bps as u64);
+    } else if utilization_rate_bps == end.utilization_rate_bps {
+        return fraction_from_bps(end.borrow_rate_bps as u64);
+    }
+
+    let slope_nom = (end.borrow_rate_bps - start.borrow_rate_bps) as u128;
+    let slope_denom = (end.utilization_rate_bps - start.utilization_rate_bps) as u128;
+    let coef = utilization_rate - fraction_from_bps(start.utilization_rate_bps as u64);
+
+    coef * slope_nom / slope_denom + fraction_from_bps(start.borrow_rate_bps as u64)
+}
+
+/// (1 + rate / SLOTS_PER_YEAR) ^ slots, approximated by the first terms of the binomial expansion.
+pub fn approximate_compounded_interest(rate: Fraction, slots: u64) -> Fraction {
+    let base = rate / SLOTS_PER_YEAR as u128;
+    match slots {
+        0 => return Fraction::ONE,
+        1 => return Fraction::ONE + base,
+        2 => return (Fraction::ONE + base) * (Fraction::ONE + base),
+        3 => return (Fraction::ONE + base) * (Fraction::ONE + base) * (Fraction::ONE + base),
+        4 => {
+            let pow_two = (Fraction::ONE + base) * (Fraction::ONE + base);
+            return pow_two * pow_two;
+        }
+        _ => {}
+    }
+
+    let exp = slots as u128;
+    let base_power_two = base * base;
+    let base_power_three = base_power_two * base;
+
+    let first_term = base * exp;
+    let second_term = base_power_two * exp * (exp - 1) / 2;
+    let third_term = base_power_three * exp * (exp - 1) * (exp - 2) / 6;
+
+    Fraction::ONE + first_term + second_term + third_term
+}
+
+/// Projects the reserve liquidity to `current_slot` the way refresh_reserve does.
+pub fn accrue_interest(reserve: &mut Reserve, current_slot: u64, referral_fee_bps: u16) {
+    // Fixtures may have been refreshed after the pinned test clock
+    let slots_elapsed = current_slot.saturating_sub(reserve.last_update.slot);
+    if slots_elapsed == 0 {
+        return;
+    }
+
+    let borrow_rate = current_borrow_rate(reserve);
+    let host_fixed_interest_rate =
+        fraction_from_bps(reserve.config.host_fixed_interest_rate_bps as u64);
+    let protocol_take_rate = fraction_from_percent(reserve.config.protocol_take_rate_pct as u64);
+    let absolute_referral_rate = protocol_take_rate * fraction_from_bps(referral_fee_bps as u64);
+    let relative_protocol_take_rate = protocol_take_rate - absolute_referral_rate;
+
+    let compounded_interest_rate =
+        approximate_compounded_interest(borrow_rate + host_fixed_interest_rate, slots_elapsed);
+    let compounded_fixed_rate =
+        approximate_compounded_interest(host_fixed_interest_rate, slots_elapsed);
+
+    let previous_debt = Fraction::from_bits(reserve.liquidity.borrowed_amount_sf);
+    let new_debt = previous_debt * compounded_interest_rate;
+    let fixed_host_fee = previous_debt * compounded_fixed_rate - previous_debt;
+    let net_new_debt = new_debt - previous_debt - fixed_host_fee;
+
+    let protocol_fees = Fraction::from_bits(reserve.liquidity.accumulated_protocol_fees_sf)
+        + fixed_host_fee
+        + net_new_debt * relative_protocol_take_rate;
+    let pending_referrer_fees = Fraction::from_bits(reserve.liquidity.pending_referrer_fees_sf)
+        + net_new_debt * absolute_referral_rate;
+
+    let cumulative_borrow_rate =
+        big_fraction(&reserve.liquidity.cumulative_borrow_rate_bsf) * compounded_interest_rate;
+    let bits = cumulative_borrow_rate.to_bits();
+    reserve.liquidity.cumulative_borrow_rate_bsf.value = [bits as u64, (bits >> 64) as u64, 0, 0];
+
+    reserve.liquidity.borrowed_amount_sf = new_debt.to_bits();
+    reserve.liquidity.accumulated_protocol_fees_sf = protocol_fees.to_bits();
+    reserve.liquidity.pending_referrer_fees_sf = pending_referrer_fees.to_bits();
+    reserve.last_update.slot = current_slot;
+}
+
+/// Reserve state after `slots_elapsed` slots without any other activity, as one refresh_reserve
+/// would leave it. Refreshing more often compounds slightly differently.
+pub fn simulate_accrual(reserve: &Reserve, slots_elapsed: u64, referral_fee_bps: u16) -> Reserve {
+    let mut reserve = reserve.clone();
+    let slot = reserve.last_update.slot + slots_elapsed;
+    accrue_interest(&mut reserve, slot, referral_fee_bps);
+
+    reserve
+}
+
+pub fn slots_from_seconds(seconds: u64) -> u64 {
+    seconds * SLOTS_PER_YEAR / (365 * 24 * 3600)
+}
+
+/// Cumulative borrow rates stay far below 2^68, the upper 128 bits are always zero.
+pub fn big_fraction(value: &BigFractionBytes) -> Fraction {
+    assert_eq!(value.value[2..], [0, 0]);
+
+    Fraction::from_bits(value.value[0] as u128 | (value.value[1] as u128) << 64)
+}
+
+/// Debt of an obligation borrow at the cumulative borrow rate of its (accrued) reserve.
+pub fn obligation_debt(liquidity: &ObligationLiquidity, reserve: &Reserve) -> Fraction {
+    let compounded_interest_rate = big_fraction(&reserve.liquidity.cumulative_borrow_rate_bsf)
+        / big_fraction(&liquidity.cumulative_borrow_rate_bsf);
+
+    Fraction::from_bits(liquidity.borrowed_amount_sf) * compounded_interest_rate
+}
+
+/// Collateral tokens per liquidity token.
+pub fn collateral_exchange_rate(reserve: &Reserve) -> Fraction {
+    let total_supply = total_supply(reserve);
+    if reserve.collateral.mint_total_supply == 0 || total_supply == Fraction::ZERO {
+        return Fraction::ONE;
+    }
+
+    Fraction::from(reserve.collateral.mint_total_supply) / total_supply
+}
+
+pub fn liquidity_to_collateral(reserve: &Reserve, liquidity_amount: u64) -> u64 {
+    (collateral_exchange_rate(reserve) * liquidity_amount as u128).to_num()
+}
+
+pub fn collateral_to_liquidity(reserve: &Reserve, collateral_amount: u64) -> u64 {
+    (Fraction::from(collateral_amount) / collateral_exchange_rate(reserve)).to_num()
+}
+
+pub fn market_price(reserve: &Reserve) -> Fraction {
+    Fraction::from_bits(reserve.liquidity.market_price_sf)
+}
+
+/// Value in the market quote currency of `amount` liquidity tokens at the reserve market price.
+pub fn market_value(reserve: &Reserve, amount: u64) -> Fraction {
+    Fraction::from(amount) / 10u64.pow(reserve.liquidity.mint_decimals as u32) as u128
+        * market_price(reserve)
+}
+
+/// Liquidity tokens worth `value` at the reserve market price, rounded down.
+pub fn amount_from_value(reserve: &Reserve, value: Fraction) -> u64 {
+    (value / market_price(reserve) * 10u64.pow(reserve.liquidity.mint_decimals as u32) as u128)
+        .to_num()
+}
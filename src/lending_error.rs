@@ -0,0 +1,166 @@
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+use solana_sdk::transport::TransportError;
+
+// Anchor numbers custom errors from 6000, in declaration order of klend's LendingError
+pub const LENDING_ERROR_OFFSET: u32 = 6000;
+
+macro_rules! lending_errors {
+    ($($name:ident),* $(,)?) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum LendingError {
+            $($name),*
+        }
+
+        const LENDING_ERRORS: &[LendingError] = &[$(LendingError::$name),*];
+    };
+}
+
+lending_errors!(
+    InvalidMarketAuthority,
+    InvalidMarketOwner,
+    InvalidAccountOwner,
+    InvalidAmount,
+    InvalidConfig,
+    InvalidSigner,
+    InvalidAccountInput,
+    MathOverflow,
+    InsufficientLiquidity,
+    ReserveStale,
+    WithdrawTooSmall,
+    WithdrawTooLarge,
+    BorrowTooSmall,
+    BorrowTooLarge,
+    RepayTooSmall,
+    LiquidationTooSmall,
+    ObligationHealthy,
+    ObligationStale,
+    ObligationReserveLimit,
+    InvalidObligationOwner,
+    ObligationDepositsEmpty,
+    ObligationBorrowsEmpty,
+    ObligationDepositsZero,
+    ObligationBorrowsZero,
+    InvalidObligationCollateral,
+    InvalidObligationLiquidity,
+    ObligationCollateralEmpty,
+    ObligationLiquidityEmpty,
+    NegativeInterestRate,
+    InvalidOracleConfig,
+    InsufficientProtocolFeesToRedeem,
+    FlashBorrowCpi,
+    NoFlashRepayFound,
+    InvalidFlashRepay,
+    FlashRepayCpi,
+    MultipleFlashBorrows,
+    FlashLoansDisabled,
+    SwitchboardV2Error,
+    CouldNotDeserializeScope,
+    PriceTooOld,
+    PriceTooDivergentFromTwap,
+    InvalidTwapPrice,
+    GlobalEmergencyMode,
+    InvalidFlag,
+    PriceNotValid,
+    PriceIsBiggerThanHeuristic,
+    PriceIsLowerThanHeuristic,
+    PriceIsZero,
+    PriceConfidenceTooWide,
+    IntegerOverflow,
+    NoFarmForReserve,
+    IncorrectInstructionInPosition,
+    NoPriceFound,
+    InvalidTwapConfig,
+    InvalidPythPriceAccount,
+    InvalidSwitchboardAccount,
+    InvalidScopePriceAccount,
+    ObligationCollateralLtvZero,
+    InvalidObligationSeedsValue,
+    InvalidObligationId,
+    InvalidBorrowRateCurvePoint,
+    InvalidUtilizationRate,
+    CannotSocializeObligationWithCollateral,
+    ObligationEmpty,
+    WithdrawalCapReached,
+    LastTimestampGreaterThanCurrent,
+    LiquidationRewardTooSmall,
+    IsolatedAssetTierViolation,
+    InconsistentElevationGroup,
+    InvalidElevationGroup,
+    InvalidElevationGroupConfig,
+    UnhealthyElevationGroupLtv,
+    ElevationGroupNewLoansDisabled,
+    ReserveDeprecated,
+    ReferrerAccountNotInitialized,
+    ReferrerAccountMintMissmatch,
+    ReferrerAccountWrongAddress,
+    ReferrerAccountReferrerMissmatch,
+    ReferrerAccountMissing,
+    InsufficientReferralFeesToRedeem,
+    CpiDisabled,
+    ShortUrlNotAsciiAlphanumeric,
+    ReserveObsolete,
+    ElevationGroupAlreadyActivated,
+    ObligationInDeprecatedReserve,
+    ReferrerStateOwnerMismatch,
+    UserMetadataOwnerAlreadySet,
+    CollateralNonLiquidatable,
+    BorrowingDisabled,
+    BorrowLimitExceeded,
+    DepositLimitExceeded,
+    BorrowingDisabledOutsideElevationGroup,
+    NetValueRemainingTooSmall,
+    WorseLTVBlocked,
+    LiabilitiesBiggerThanAssets,
+    ReserveTokenBalanceMismatch,
+    ReserveVaultBalanceMismatch,
+    ReserveAccountingMismatch,
+    BorrowingAboveUtilizationRateDisabled,
+    LiquidationBorrowFactorPriority,
+    LiquidationLowestLTVPriority,
+    ElevationGroupBorrowLimitExceeded,
+    ElevationGroupWithoutDebtReserve,
+    ElevationGroupMaxCollateralReserveZero,
+    ElevationGroupHasAnotherDebtReserve,
+    ElevationGroupDebtReserveAsCollateral,
+    ObligationCollateralExceedsElevationGroupLimit,
+    ObligationElevationGroupMultipleDebtReserve,
+    UnsupportedTokenExtension,
+    InvalidTokenAccount,
+    DepositDisabledOutsideElevationGroup,
+    CannotCalculateReferralAmountDueToSlotsMismatch,
+);
+
+impl LendingError {
+    pub fn from_code(code: u32) -> Option<LendingError> {
+        code.checked_sub(LENDING_ERROR_OFFSET)
+            .and_then(|index| LENDING_ERRORS.get(index as usize))
+            .copied()
+    }
+
+    pub fn code(&self) -> u32 {
+        LENDING_ERROR_OFFSET + *self as u32
+    }
+}
+
+/// The klend error a transaction failed with, None if it succeeded or failed otherwise.
+pub fn klend_error(result: &Result<(), TransportError>) -> Option<LendingError> {
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        ))) => LendingError::from_code(*code),
+        _ => None,
+    }
+}
+
+pub fn assert_klend_error(result: Result<(), TransportError>, expected: LendingError) {
+    assert_eq!(
+        klend_error(&result),
+        Some(expected),
+        "expected klend error {:?} ({}), transaction result: {:?}",
+        expected,
+        expected.code(),
+        result
+    );
+}
@@ -0,0 +1,25 @@
+//! klend and kfarm instruction builders, PDA derivations, account decoders and math. The
+//! ProgramTest harness (`helper`, `lookup_table` and `context`) needs the `test-harness` feature,
+//! it loads programs and accounts from the `tests/fixtures` directory of the dependent crate.
+
+pub mod account_diff;
+pub mod accounts;
+#[cfg(feature = "test-harness")]
+pub mod context;
+pub mod decoder;
+#[cfg(feature = "test-harness")]
+pub mod helper;
+pub mod kamino;
+pub mod kfarm_instructions;
+pub mod kfarm_state;
+pub mod klend_instructions;
+pub mod klend_math;
+pub mod lending_error;
+#[cfg(feature = "test-harness")]
+pub mod lookup_table;
+pub mod obligation_health;
+pub mod reserve_registry;
+pub mod reserve_snapshot;
+pub mod scope;
+pub mod tx_report;
+pub mod tx_size;
@@ -0,0 +1,72 @@
+use solana_program::address_lookup_table::instruction::{create_lookup_table, extend_lookup_table};
+use solana_program::address_lookup_table::state::AddressLookupTable;
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::pubkey::Pubkey;
+use solana_program::slot_hashes::SlotHashes;
+use solana_program_test::ProgramTestContext;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signer;
+
+use super::helper::{get_account, process_instructions};
+
+// Keeps each extend transaction well below the legacy size limit
+const EXTEND_CHUNK: usize = 20;
+
+/// The lookup table program only accepts a slot still in SlotHashes, the current one is not yet.
+pub async fn recent_slot(context: &mut ProgramTestContext) -> u64 {
+    let slot_hashes: SlotHashes = context.banks_client.get_sysvar().await.unwrap();
+
+    slot_hashes.first().unwrap().0
+}
+
+pub async fn compose_create_lookup_table_ix(
+    context: &mut ProgramTestContext,
+    authority: &Pubkey,
+) -> (Instruction, Pubkey) {
+    let slot = recent_slot(context).await;
+
+    create_lookup_table(*authority, *authority, slot)
+}
+
+/// Appends the addresses missing from the table, they can be used from the next slot on.
+pub async fn extend_table(
+    context: &mut ProgramTestContext,
+    authority: &Keypair,
+    table: &Pubkey,
+    addresses: &[Pubkey],
+) {
+    let existing = read_lookup_table(context, table).await.addresses;
+    let mut missing: Vec<Pubkey> = vec![];
+    for address in addresses {
+        if !existing.contains(address) && !missing.contains(address) {
+            missing.push(*address);
+        }
+    }
+
+    for chunk in missing.chunks(EXTEND_CHUNK) {
+        let instruction = extend_lookup_table(
+            *table,
+            authority.pubkey(),
+            Some(authority.pubkey()),
+            chunk.to_vec(),
+        );
+
+        process_instructions(context, authority, &[instruction]).await;
+    }
+}
+
+pub async fn read_lookup_table(
+    context: &mut ProgramTestContext,
+    table: &Pubkey,
+) -> AddressLookupTableAccount {
+    let account = get_account(&mut context.banks_client, *table)
+        .await
+        .unwrap_or_else(|| panic!("lookup table {} does not exist", table));
+    let state = AddressLookupTable::deserialize(&account.data).unwrap();
+
+    AddressLookupTableAccount {
+        key: *table,
+        addresses: state.addresses.to_vec(),
+    }
+}
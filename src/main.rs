@@ -1,3 +1,112 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use integrate_klend_2::accounts::{read_account_dump, KaminoAccount};
+use integrate_klend_2::decoder::decode_transaction;
+use solana_program::address_lookup_table::state::AddressLookupTable;
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+use std::path::Path;
+use std::str::FromStr;
+use std::{env, fs, process};
+
+const USAGE: &str = "usage:
+    integrate-klend-2 decode-tx <base64 transaction> [<lookup table>.bin ...]
+    integrate-klend-2 inspect [--json] <account dump> ...
+
+decode-tx  prints the klend and kfarm instructions of a serialized transaction, with the role
+           of each account. Lookup tables of a v0 transaction are account dumps named after
+           their address, like the files in tests/fixtures.
+
+inspect    prints the decoded LendingMarket, Reserve, Obligation or kfarm FarmState of account
+           dumps, either the raw data written by `solana account --output-file` or the JSON
+           of `solana account --output json`. --json prints one JSON object per account.";
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("decode-tx") if args.len() >= 2 => decode_tx(&args[1], &args[2..]),
+        Some("inspect") if args.len() >= 2 => {
+            let json = args[1] == "--json";
+            let files = if json { &args[2..] } else { &args[1..] };
+            if files.is_empty() {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+            inspect(files, json)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn decode_tx(encoded: &str, lookup_table_files: &[String]) -> Result<(), String> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| format!("transaction is not base64: {}", e))?;
+    let transaction: VersionedTransaction =
+        bincode::deserialize(&bytes).map_err(|e| format!("not a serialized transaction: {}", e))?;
+
+    let lookup_tables = lookup_table_files
+        .iter()
+        .map(|file| read_lookup_table(file))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (i, instruction) in decode_transaction(&transaction, &lookup_tables)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .enumerate()
+    {
+        print!("#{} {}", i, instruction);
+    }
+
+    Ok(())
+}
+
+fn inspect(files: &[String], json: bool) -> Result<(), String> {
+    for file in files {
+        let dump = read_account_dump(Path::new(file))?;
+        let account = KaminoAccount::decode(&dump.data).ok_or_else(|| {
+            format!(
+                "{} is not a klend LendingMarket, Reserve, Obligation or kfarm FarmState",
+                file
+            )
+        })?;
+
+        if json {
+            println!("{}", account.to_json(dump.address.as_ref()));
+        } else {
+            match dump.address {
+                Some(address) => print!("{} {}", address, account),
+                None => print!("{} {}", file, account),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_lookup_table(file: &str) -> Result<AddressLookupTableAccount, String> {
+    let path = Path::new(file);
+    let key = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| Pubkey::from_str(stem).ok())
+        .ok_or_else(|| format!("{} is not named after a lookup table address", file))?;
+    let data = fs::read(path).map_err(|e| format!("unable to read {}: {}", file, e))?;
+    let table = AddressLookupTable::deserialize(&data)
+        .map_err(|e| format!("{} is not a lookup table: {}", file, e))?;
+
+    Ok(AddressLookupTableAccount {
+        key,
+        addresses: table.addresses.to_vec(),
+    })
 }
@@ -0,0 +1,242 @@
+use crate::klend_math::{
+    amount_from_value, collateral_exchange_rate, fraction_from_percent, market_price,
+    obligation_debt,
+};
+use fixed::types::U68F60 as Fraction;
+use klend::state::{ElevationGroup, LendingMarket, Obligation, Reserve};
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct BorrowCapacity {
+    pub reserve: Pubkey,
+    /// Liquidity that can still be borrowed before reaching the max LTV
+    pub amount: u64,
+}
+
+#[derive(Debug)]
+pub struct LiquidationPrice {
+    pub reserve: Pubkey,
+    pub price: Fraction,
+    /// Collateral price at which the obligation becomes liquidatable, other prices unchanged.
+    /// None if the obligation stays healthy down to a zero price.
+    pub liquidation_price: Option<Fraction>,
+}
+
+/// Health of an obligation computed from its deposits and borrows at the reserve prices,
+/// the way refresh_obligation does it. Inside an elevation group the collateral LTV and
+/// liquidation threshold are the group ones, and debts are not borrow factor adjusted.
+#[derive(Debug)]
+pub struct ObligationHealth {
+    pub deposited_value: Fraction,
+    pub borrowed_value: Fraction,
+    pub borrow_factor_adjusted_debt_value: Fraction,
+    pub allowed_borrow_value: Fraction,
+    pub unhealthy_borrow_value: Fraction,
+    /// Borrow factor adjusted debt value / deposited value
+    pub ltv: Fraction,
+    pub max_ltv: Fraction,
+    pub liquidation_ltv: Fraction,
+    /// Market value of each deposit
+    pub deposits: Vec<(Pubkey, Fraction)>,
+    /// Market value of each borrow, not borrow factor adjusted
+    pub borrows: Vec<(Pubkey, Fraction)>,
+    pub borrow_capacity: Vec<BorrowCapacity>,
+    pub liquidation_prices: Vec<LiquidationPrice>,
+}
+
+impl ObligationHealth {
+    /// `reserves` holds every deposit and borrow reserve of the obligation, ideally projected to
+    /// the current slot. Borrow capacity is reported for each of them.
+    pub fn new(
+        obligation: &Obligation,
+        market: &LendingMarket,
+        reserves: &HashMap<Pubkey, Reserve>,
+    ) -> ObligationHealth {
+        Self::try_new(obligation, market, reserves)
+            .unwrap_or_else(|address| panic!("missing obligation reserve {}", address))
+    }
+
+    /// Fails with the first reserve of the obligation missing from `reserves`.
+    pub fn try_new(
+        obligation: &Obligation,
+        market: &LendingMarket,
+        reserves: &HashMap<Pubkey, Reserve>,
+    ) -> Result<ObligationHealth, Pubkey> {
+        let missing = obligation
+            .deposits
+            .iter()
+            .map(|d| d.deposit_reserve)
+            .chain(obligation.borrows.iter().map(|b| b.borrow_reserve))
+            .find(|address| *address != Pubkey::default() && !reserves.contains_key(address));
+        if let Some(address) = missing {
+            return Err(address);
+        }
+
+        let group = obligation_elevation_group(obligation, market);
+        let reserve = |address: &Pubkey| &reserves[address];
+
+        let mut deposited_value = Fraction::ZERO;
+        let mut allowed_borrow_value = Fraction::ZERO;
+        let mut unhealthy_borrow_value = Fraction::ZERO;
+        let mut deposits: Vec<(Pubkey, Fraction)> = vec![];
+        for deposit in obligation
+            .deposits
+            .iter()
+            .filter(|d| d.deposit_reserve != Pubkey::default())
+        {
+            let state = reserve(&deposit.deposit_reserve);
+            let liquidity =
+                Fraction::from(deposit.deposited_amount) / collateral_exchange_rate(state);
+            let value = liquidity_value(state, liquidity);
+
+            deposited_value += value;
+            allowed_borrow_value += value * max_ltv(state, group);
+            unhealthy_borrow_value += value * liquidation_threshold(state, group);
+            deposits.push((deposit.deposit_reserve, value));
+        }
+
+        let mut borrowed_value = Fraction::ZERO;
+        let mut borrow_factor_adjusted_debt_value = Fraction::ZERO;
+        let mut borrows: Vec<(Pubkey, Fraction)> = vec![];
+        for borrow in obligation
+            .borrows
+            .iter()
+            .filter(|b| b.borrow_reserve != Pubkey::default())
+        {
+            let state = reserve(&borrow.borrow_reserve);
+            let value = liquidity_value(state, obligation_debt(borrow, state));
+
+            borrowed_value += value;
+            borrow_factor_adjusted_debt_value += value * borrow_factor(state, group);
+            borrows.push((borrow.borrow_reserve, value));
+        }
+
+        let ratio = |value: Fraction| {
+            if deposited_value == Fraction::ZERO {
+                Fraction::ZERO
+            } else {
+                value / deposited_value
+            }
+        };
+
+        let remaining_borrow_value = allowed_borrow_value
+            .checked_sub(borrow_factor_adjusted_debt_value)
+            .unwrap_or(Fraction::ZERO);
+        let mut borrow_capacity: Vec<BorrowCapacity> = reserves
+            .iter()
+            .map(|(address, state)| BorrowCapacity {
+                reserve: *address,
+                amount: amount_from_value(
+                    state,
+                    remaining_borrow_value / borrow_factor(state, group),
+                ),
+            })
+            .collect();
+        borrow_capacity.sort_by_key(|c| c.reserve);
+
+        // The deposit value scales with its price, the rest of the obligation stays as is.
+        // A worthless deposit or one without threshold can't move the obligation health.
+        let liquidation_prices = deposits
+            .iter()
+            .map(|(address, value)| {
+                let state = reserve(address);
+                let threshold = liquidation_threshold(state, group);
+                let others = unhealthy_borrow_value - *value * threshold;
+                let liquidation_price = borrow_factor_adjusted_debt_value
+                    .checked_sub(others)
+                    .filter(|shortfall| *shortfall > Fraction::ZERO)
+                    .filter(|_| *value * threshold > Fraction::ZERO)
+                    .map(|shortfall| market_price(state) * shortfall / (*value * threshold));
+
+                LiquidationPrice {
+                    reserve: *address,
+                    price: market_price(state),
+                    liquidation_price,
+                }
+            })
+            .collect();
+
+        Ok(ObligationHealth {
+            deposited_value,
+            borrowed_value,
+            borrow_factor_adjusted_debt_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            ltv: ratio(borrow_factor_adjusted_debt_value),
+            max_ltv: ratio(allowed_borrow_value),
+            liquidation_ltv: ratio(unhealthy_borrow_value),
+            deposits,
+            borrows,
+            borrow_capacity,
+            liquidation_prices,
+        })
+    }
+
+    pub fn is_liquidatable(&self) -> bool {
+        self.borrow_factor_adjusted_debt_value >= self.unhealthy_borrow_value
+            && self.borrow_factor_adjusted_debt_value > Fraction::ZERO
+    }
+
+    pub fn borrow_capacity(&self, reserve: &Pubkey) -> u64 {
+        self.borrow_capacity
+            .iter()
+            .find(|c| c.reserve == *reserve)
+            .map(|c| c.amount)
+            .unwrap_or_else(|| panic!("no borrow capacity for reserve {}", reserve))
+    }
+
+    pub fn liquidation_price(&self, reserve: &Pubkey) -> Option<Fraction> {
+        self.liquidation_prices
+            .iter()
+            .find(|p| p.reserve == *reserve)
+            .unwrap_or_else(|| panic!("reserve {} is not a deposit", reserve))
+            .liquidation_price
+    }
+}
+
+/// The elevation group the obligation is in, None outside of one.
+pub fn obligation_elevation_group<'a>(
+    obligation: &Obligation,
+    market: &'a LendingMarket,
+) -> Option<&'a ElevationGroup> {
+    if obligation.elevation_group == 0 {
+        return None;
+    }
+    let group = market
+        .elevation_groups
+        .iter()
+        .find(|group| group.id == obligation.elevation_group);
+    assert!(
+        group.is_some(),
+        "unknown elevation group {}",
+        obligation.elevation_group
+    );
+    group
+}
+
+/// Collateral loan-to-value, the one of the elevation group inside of a group.
+pub fn max_ltv(reserve: &Reserve, group: Option<&ElevationGroup>) -> Fraction {
+    let pct = group.map_or(reserve.config.loan_to_value_pct, |group| group.ltv_pct);
+    fraction_from_percent(pct as u64)
+}
+
+/// Collateral liquidation threshold, the one of the elevation group inside of a group.
+pub fn liquidation_threshold(reserve: &Reserve, group: Option<&ElevationGroup>) -> Fraction {
+    let pct = group.map_or(reserve.config.liquidation_threshold_pct, |group| {
+        group.liquidation_threshold_pct
+    });
+    fraction_from_percent(pct as u64)
+}
+
+/// Debt borrow factor, klend doesn't apply it inside of an elevation group.
+pub fn borrow_factor(reserve: &Reserve, group: Option<&ElevationGroup>) -> Fraction {
+    match group {
+        Some(_) => Fraction::ONE,
+        None => fraction_from_percent(reserve.config.borrow_factor_pct),
+    }
+}
+
+fn liquidity_value(reserve: &Reserve, liquidity: Fraction) -> Fraction {
+    liquidity / 10u64.pow(reserve.liquidity.mint_decimals as u32) as u128 * market_price(reserve)
+}
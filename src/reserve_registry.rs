@@ -0,0 +1,181 @@
+use borsh::BorshDeserialize;
+use klend::state::Reserve;
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+
+use crate::accounts::RESERVE_DISCRIMINATOR;
+use crate::kamino::{Asset, FarmMode};
+
+/// Account data that can't be read as a klend `Reserve`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReserveDataError {
+    NotAReserve, // Too short or another account discriminator
+    InvalidLayout,
+}
+
+impl fmt::Display for ReserveDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReserveDataError::NotAReserve => write!(f, "account is not a klend reserve"),
+            ReserveDataError::InvalidLayout => {
+                write!(f, "reserve data doesn't match the Reserve layout")
+            }
+        }
+    }
+}
+
+/// Every address and static attribute of a klend reserve, read from its on-chain `Reserve` state.
+pub struct ReserveInfo {
+    pub address: Pubkey,
+    pub lending_market: Pubkey,
+    pub symbol: String,
+    pub decimals: u8,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_supply_vault: Pubkey,
+    pub liquidity_fee_vault: Pubkey,
+    pub liquidity_token_program: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_supply_vault: Pubkey,
+    pub farm_collateral: Pubkey, // Pubkey::default() if the reserve has no farm
+    pub farm_debt: Pubkey,       // Pubkey::default() if the reserve has no farm
+    pub scope_prices: Pubkey,
+    pub scope_price_chain: [u16; 4],
+    pub scope_twap_chain: [u16; 4],
+    pub pyth_price: Pubkey,
+    pub switchboard_price_aggregator: Pubkey,
+    pub switchboard_twap_aggregator: Pubkey,
+}
+
+impl ReserveInfo {
+    pub fn from_account_data(
+        address: &Pubkey,
+        data: &[u8],
+    ) -> Result<ReserveInfo, ReserveDataError> {
+        if data.len() < 8 || data[..8] != RESERVE_DISCRIMINATOR {
+            return Err(ReserveDataError::NotAReserve);
+        }
+        let reserve =
+            Reserve::try_from_slice(&data[8..]).map_err(|_| ReserveDataError::InvalidLayout)?;
+        let token_info = &reserve.config.token_info;
+
+        Ok(ReserveInfo {
+            address: *address,
+            lending_market: reserve.lending_market,
+            symbol: String::from_utf8_lossy(&token_info.name)
+                .trim_end_matches('\0')
+                .to_string(),
+            decimals: reserve.liquidity.mint_decimals as u8,
+            liquidity_mint: reserve.liquidity.mint_pubkey,
+            liquidity_supply_vault: reserve.liquidity.supply_vault,
+            liquidity_fee_vault: reserve.liquidity.fee_vault,
+            liquidity_token_program: reserve.liquidity.token_program,
+            collateral_mint: reserve.collateral.mint_pubkey,
+            collateral_supply_vault: reserve.collateral.supply_vault,
+            farm_collateral: reserve.farm_collateral,
+            farm_debt: reserve.farm_debt,
+            scope_prices: token_info.scope_configuration.price_feed,
+            scope_price_chain: token_info.scope_configuration.price_chain,
+            scope_twap_chain: token_info.scope_configuration.twap_chain,
+            pyth_price: token_info.pyth_configuration.price,
+            switchboard_price_aggregator: token_info.switchboard_configuration.price_aggregator,
+            switchboard_twap_aggregator: token_info.switchboard_configuration.twap_aggregator,
+        })
+    }
+
+    /// Farms in `detached_farms` are removed from the reserve, the fixtures are then loaded
+    /// without them. Panics on any other farm without a fixture file.
+    #[cfg(feature = "test-harness")]
+    pub fn from_fixture(address: &Pubkey, detached_farms: &[Pubkey]) -> ReserveInfo {
+        let data = crate::helper::read_account_data(&format!("{}.bin", address));
+        let mut info = ReserveInfo::from_account_data(address, &data)
+            .unwrap_or_else(|e| panic!("reserve fixture {}: {}", address, e));
+
+        for farm in [&mut info.farm_collateral, &mut info.farm_debt] {
+            if *farm == Pubkey::default() {
+                continue;
+            }
+            if detached_farms.contains(farm) {
+                *farm = Pubkey::default();
+            } else {
+                assert!(
+                    solana_program_test::find_file(&format!("{}.bin", farm)).is_some(),
+                    "farm {} of reserve {} has no fixture file, add it or detach it",
+                    farm,
+                    info.symbol
+                );
+            }
+        }
+
+        info
+    }
+
+    pub fn has_farm_collateral(&self) -> bool {
+        self.farm_collateral != Pubkey::default()
+    }
+
+    pub fn has_farm_debt(&self) -> bool {
+        self.farm_debt != Pubkey::default()
+    }
+
+    pub fn farm(&self, mode: FarmMode) -> Pubkey {
+        match mode {
+            FarmMode::Collateral => self.farm_collateral,
+            FarmMode::Debt => self.farm_debt,
+        }
+    }
+}
+
+/// Known reserves, keyed by reserve address.
+pub struct ReserveRegistry {
+    reserves: Vec<ReserveInfo>,
+}
+
+impl ReserveRegistry {
+    pub fn new(reserves: Vec<ReserveInfo>) -> ReserveRegistry {
+        ReserveRegistry { reserves }
+    }
+
+    /// See `ReserveInfo::from_fixture` for `detached_farms`.
+    #[cfg(feature = "test-harness")]
+    pub fn from_fixtures(addresses: &[Pubkey], detached_farms: &[Pubkey]) -> ReserveRegistry {
+        ReserveRegistry {
+            reserves: addresses
+                .iter()
+                .map(|address| ReserveInfo::from_fixture(address, detached_farms))
+                .collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ReserveInfo> {
+        self.reserves.iter()
+    }
+
+    pub fn get(&self, address: &Pubkey) -> &ReserveInfo {
+        self.reserves
+            .iter()
+            .find(|r| r.address == *address)
+            .unwrap_or_else(|| panic!("reserve {} is not registered", address))
+    }
+
+    pub fn asset(&self, asset: Asset) -> &ReserveInfo {
+        self.get(&asset.reserve())
+    }
+
+    pub fn market_reserves(&self, market: &Pubkey) -> impl Iterator<Item = &ReserveInfo> + '_ {
+        let market = *market;
+
+        self.reserves
+            .iter()
+            .filter(move |r| r.lending_market == market)
+    }
+
+    pub fn find_by_liquidity_mint(&self, market: &Pubkey, mint: &Pubkey) -> Option<&ReserveInfo> {
+        self.market_reserves(market)
+            .find(|r| r.liquidity_mint == *mint)
+    }
+
+    pub fn find_by_symbol(&self, market: &Pubkey, symbol: &str) -> Option<&ReserveInfo> {
+        self.market_reserves(market)
+            .find(|r| r.symbol.eq_ignore_ascii_case(symbol))
+    }
+}
@@ -0,0 +1,113 @@
+use crate::klend_math::{
+    borrow_rate_at, collateral_exchange_rate, collateral_to_liquidity, fraction_from_bps,
+    fraction_from_percent, liquidity_to_collateral, market_price, total_supply, utilization_rate,
+    SLOTS_PER_YEAR,
+};
+#[cfg(feature = "test-harness")]
+use borsh::BorshDeserialize;
+use fixed::types::U68F60 as Fraction;
+use klend::state::Reserve;
+#[cfg(feature = "test-harness")]
+use solana_program::pubkey::Pubkey;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RatePoint {
+    pub utilization_rate: Fraction,
+    pub borrow_apy: f64,
+    pub supply_apy: f64,
+}
+
+/// Analytics of a reserve state, as of its last update unless it was projected beforehand.
+#[derive(Debug)]
+pub struct ReserveSnapshot {
+    pub reserve: Reserve,
+    pub market_price: Fraction,
+    pub available_amount: u64,
+    pub borrowed_amount: Fraction,
+    /// Available + borrowed liquidity, net of protocol and referrer fees
+    pub total_supply: Fraction,
+    pub utilization_rate: Fraction,
+    pub borrow_apr: Fraction,
+    pub supply_apr: Fraction,
+    pub borrow_apy: f64,
+    pub supply_apy: f64,
+    /// Collateral tokens per liquidity token
+    pub collateral_exchange_rate: Fraction,
+}
+
+impl ReserveSnapshot {
+    pub fn new(reserve: &Reserve) -> ReserveSnapshot {
+        let utilization_rate = utilization_rate(reserve);
+        let borrow_apr = borrow_apr_at(reserve, utilization_rate);
+        let supply_apr = supply_apr_at(reserve, utilization_rate);
+
+        ReserveSnapshot {
+            reserve: reserve.clone(),
+            market_price: market_price(reserve),
+            available_amount: reserve.liquidity.available_amount,
+            borrowed_amount: Fraction::from_bits(reserve.liquidity.borrowed_amount_sf),
+            total_supply: total_supply(reserve),
+            utilization_rate,
+            borrow_apr,
+            supply_apr,
+            borrow_apy: apy(borrow_apr),
+            supply_apy: apy(supply_apr),
+            collateral_exchange_rate: collateral_exchange_rate(reserve),
+        }
+    }
+
+    #[cfg(feature = "test-harness")]
+    pub fn from_fixture(address: &Pubkey) -> ReserveSnapshot {
+        let data = crate::helper::read_account_data(&format!("{}.bin", address));
+        let reserve = Reserve::try_from_slice(&data[8..]).unwrap(); // Skip discriminator !
+
+        ReserveSnapshot::new(&reserve)
+    }
+
+    pub fn liquidity_to_collateral(&self, liquidity_amount: u64) -> u64 {
+        liquidity_to_collateral(&self.reserve, liquidity_amount)
+    }
+
+    pub fn collateral_to_liquidity(&self, collateral_amount: u64) -> u64 {
+        collateral_to_liquidity(&self.reserve, collateral_amount)
+    }
+
+    /// Borrow and supply APYs at `steps + 1` evenly spaced utilization rates from 0 to 100%.
+    pub fn rate_curve(&self, steps: u32) -> Vec<RatePoint> {
+        assert!(steps >= 1, "a rate curve needs at least one step");
+
+        (0..=steps)
+            .map(|step| {
+                let utilization_rate = Fraction::from(step as u64) / steps as u128;
+
+                RatePoint {
+                    utilization_rate,
+                    borrow_apy: apy(borrow_apr_at(&self.reserve, utilization_rate)),
+                    supply_apy: apy(supply_apr_at(&self.reserve, utilization_rate)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Curve rate plus the host fixed rate, as charged to borrowers.
+pub fn borrow_apr_at(reserve: &Reserve, utilization_rate: Fraction) -> Fraction {
+    borrow_rate_at(reserve, utilization_rate)
+        + fraction_from_bps(reserve.config.host_fixed_interest_rate_bps as u64)
+}
+
+/// Interest paid on the curve rate, less the protocol take rate, spread over the whole supply.
+pub fn supply_apr_at(reserve: &Reserve, utilization_rate: Fraction) -> Fraction {
+    let protocol_take_rate = fraction_from_percent(reserve.config.protocol_take_rate_pct as u64);
+
+    borrow_rate_at(reserve, utilization_rate)
+        * utilization_rate.min(Fraction::ONE)
+        * (Fraction::ONE - protocol_take_rate)
+}
+
+/// Interest compounded every slot over a year.
+pub fn apy(apr: Fraction) -> f64 {
+    let slots = SLOTS_PER_YEAR as f64;
+
+    (1.0 + apr.to_num::<f64>() / slots).powf(slots) - 1.0
+}
@@ -0,0 +1,152 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use fixed::types::U68F60 as Fraction;
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+
+// Layout of the scope OraclePrices account (zero copy, no padding):
+// discriminator [u8; 8] | oracle_mappings Pubkey | prices [DatedPrice; 512]
+
+pub const SCOPE_MAX_ENTRIES: usize = 512;
+const DATED_PRICE_LEN: usize = 56;
+const PRICES_OFFSET: usize = 8 + 32;
+pub const SCOPE_CHAIN_END: u16 = u16::MAX;
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default)]
+pub struct ScopePrice {
+    pub value: u64,
+    pub exp: u64,
+}
+
+impl ScopePrice {
+    pub fn to_fraction(&self) -> Fraction {
+        Fraction::from(self.value) / 10u128.pow(self.exp as u32)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default)]
+pub struct DatedPrice {
+    pub price: ScopePrice,
+    pub last_updated_slot: u64,
+    pub unix_timestamp: u64,
+    pub reserved: [u64; 2],
+    pub reserved2: [u16; 3],
+    pub index: u16,
+}
+
+/// `OraclePrices` account data that doesn't match the scope layout, or a price an entry can't
+/// hold at its exponent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScopeError {
+    InvalidAccountLength { len: usize, expected: usize },
+    InvalidPriceEntry { index: usize },
+    PriceOverflow { index: u16 },
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScopeError::InvalidAccountLength { len, expected } => write!(
+                f,
+                "OraclePrices account is {} bytes, expected {}",
+                len, expected
+            ),
+            ScopeError::InvalidPriceEntry { index } => {
+                write!(f, "scope price entry {} can't be decoded", index)
+            }
+            ScopeError::PriceOverflow { index } => {
+                write!(f, "price overflows scope price entry {}", index)
+            }
+        }
+    }
+}
+
+pub struct OraclePrices {
+    discriminator: [u8; 8],
+    pub oracle_mappings: Pubkey,
+    pub prices: Vec<DatedPrice>,
+}
+
+impl OraclePrices {
+    pub fn from_account_data(data: &[u8]) -> Result<OraclePrices, ScopeError> {
+        let expected = PRICES_OFFSET + SCOPE_MAX_ENTRIES * DATED_PRICE_LEN;
+        if data.len() != expected {
+            return Err(ScopeError::InvalidAccountLength {
+                len: data.len(),
+                expected,
+            });
+        }
+
+        let prices = data[PRICES_OFFSET..]
+            .chunks(DATED_PRICE_LEN)
+            .enumerate()
+            .map(|(index, chunk)| {
+                DatedPrice::try_from_slice(chunk)
+                    .map_err(|_| ScopeError::InvalidPriceEntry { index })
+            })
+            .collect::<Result<Vec<DatedPrice>, ScopeError>>()?;
+
+        // Fixed size slices of a checked length
+        Ok(OraclePrices {
+            discriminator: data[..8].try_into().unwrap(),
+            oracle_mappings: Pubkey::try_from(&data[8..PRICES_OFFSET]).unwrap(),
+            prices,
+        })
+    }
+
+    pub fn to_account_data(&self) -> Vec<u8> {
+        let mut data = self.discriminator.to_vec();
+        data.extend_from_slice(self.oracle_mappings.as_ref());
+        for price in &self.prices {
+            data.extend(price.try_to_vec().unwrap());
+        }
+
+        data
+    }
+
+    pub fn price(&self, index: u16) -> Fraction {
+        self.prices[index as usize].price.to_fraction()
+    }
+
+    /// Product of the chain entries, the way klend prices a reserve.
+    pub fn chain_price(&self, chain: &[u16; 4]) -> Fraction {
+        chain
+            .iter()
+            .take_while(|index| **index != SCOPE_CHAIN_END)
+            .fold(Fraction::ONE, |price, index| price * self.price(*index))
+    }
+
+    /// Keeps the entry exponent, the price is rounded down to it.
+    pub fn set_price(
+        &mut self,
+        index: u16,
+        price: Fraction,
+        slot: u64,
+        unix_timestamp: u64,
+    ) -> Result<(), ScopeError> {
+        let entry = &mut self.prices[index as usize];
+        entry.price.value = scale_price(price, entry.price.exp)
+            .and_then(|value| u64::try_from(value).ok())
+            .ok_or(ScopeError::PriceOverflow { index })?;
+        entry.last_updated_slot = slot;
+        entry.unix_timestamp = unix_timestamp;
+
+        Ok(())
+    }
+
+    /// Stamps every set price with the given time, as if the scope crank just ran.
+    pub fn refresh_timestamps(&mut self, slot: u64, unix_timestamp: u64) {
+        for price in self.prices.iter_mut().filter(|p| p.price.value != 0) {
+            price.last_updated_slot = slot;
+            price.unix_timestamp = unix_timestamp;
+        }
+    }
+}
+
+/// `price * 10^exp` rounded down, None on overflow.
+fn scale_price(price: Fraction, exp: u64) -> Option<u128> {
+    let scale = 10u128.checked_pow(u32::try_from(exp).ok()?)?;
+    let int = price.int().to_num::<u128>().checked_mul(scale)?;
+    let frac = price.frac().to_bits().checked_mul(scale)? >> Fraction::FRAC_NBITS;
+
+    int.checked_add(frac)
+}
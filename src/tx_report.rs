@@ -0,0 +1,128 @@
+use super::account_diff::StateDiff;
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::message::VersionedMessage;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_sdk::transaction_context::TransactionReturnData;
+
+/// What the runtime reported about a processed transaction.
+#[derive(Debug)]
+pub struct TxReport {
+    pub result: Result<(), TransactionError>,
+    pub logs: Vec<String>,
+    pub compute_units_consumed: u64,
+    pub compute_unit_limit: u32,
+    /// Per top level instruction, compute budget included. Builtin programs don't report it and
+    /// count as 0
+    pub instruction_compute_units: Vec<u64>,
+    pub return_data: Option<TransactionReturnData>,
+    pub writable_accounts: Vec<Pubkey>,
+    /// Serialized size, a transaction must fit in 1232 bytes
+    pub transaction_size: usize,
+    /// Changes to the watched accounts, see `process_instructions_with_diff`
+    pub state_diff: Option<StateDiff>,
+}
+
+impl TxReport {
+    pub fn new(
+        transaction: &VersionedTransaction,
+        lookup_tables: &[AddressLookupTableAccount],
+        result: Result<(), TransactionError>,
+        logs: Vec<String>,
+        compute_units_consumed: u64,
+        return_data: Option<TransactionReturnData>,
+    ) -> TxReport {
+        let message = &transaction.message;
+
+        TxReport {
+            result,
+            instruction_compute_units: instruction_compute_units(
+                &logs,
+                message.instructions().len(),
+            ),
+            logs,
+            compute_units_consumed,
+            compute_unit_limit: 0,
+            return_data,
+            writable_accounts: writable_accounts(message, lookup_tables),
+            transaction_size: bincode::serialize(transaction).unwrap().len(),
+            state_diff: None,
+        }
+    }
+
+    pub fn assert_success(&self) {
+        if let Err(err) = &self.result {
+            self.print_logs();
+            panic!("transaction failed: {:?}", err);
+        }
+    }
+
+    pub fn print_logs(&self) {
+        for log in &self.logs {
+            println!("{}", log);
+        }
+    }
+}
+
+/// Reads the "Program <id> consumed <n> of <m> compute units" lines of top level invocations.
+fn instruction_compute_units(logs: &[String], instruction_count: usize) -> Vec<u64> {
+    let mut compute_units = vec![0; instruction_count];
+    let mut instruction: Option<usize> = None;
+    let mut depth = 0;
+
+    for log in logs {
+        let words: Vec<&str> = log.split_whitespace().collect();
+        match words.as_slice() {
+            ["Program", _, "invoke", level] => {
+                depth = level.trim_matches(&['[', ']'][..]).parse().unwrap();
+                if depth == 1 {
+                    instruction = Some(instruction.map_or(0, |i| i + 1));
+                }
+            }
+            ["Program", _, "consumed", units, "of", _, "compute", "units"] if depth == 1 => {
+                if let Some(slot) = instruction.and_then(|i| compute_units.get_mut(i)) {
+                    *slot = units.parse().unwrap();
+                }
+            }
+            ["Program", _, "success"] | ["Program", _, "failed:", ..] => depth -= 1,
+            _ => {}
+        }
+    }
+
+    compute_units
+}
+
+/// Accounts the transaction locks as writable: static ones from the message header, then the
+/// ones loaded from lookup tables.
+fn writable_accounts(
+    message: &VersionedMessage,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Vec<Pubkey> {
+    let header = message.header();
+    let keys = message.static_account_keys();
+    let signers = header.num_required_signatures as usize;
+    let writable_signers = signers - header.num_readonly_signed_accounts as usize;
+    let writable_end = keys.len() - header.num_readonly_unsigned_accounts as usize;
+
+    let mut writable: Vec<Pubkey> = keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i < writable_signers || (*i >= signers && *i < writable_end))
+        .map(|(_, key)| *key)
+        .collect();
+
+    for lookup in message.address_table_lookups().unwrap_or_default() {
+        let table = lookup_tables
+            .iter()
+            .find(|t| t.key == lookup.account_key)
+            .unwrap();
+        writable.extend(
+            lookup
+                .writable_indexes
+                .iter()
+                .map(|i| table.addresses[*i as usize]),
+        );
+    }
+
+    writable
+}
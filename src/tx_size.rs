@@ -0,0 +1,118 @@
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::transaction::VersionedTransaction;
+use std::fmt::Write;
+
+pub const MAX_TRANSACTION_SIZE: usize = PACKET_DATA_SIZE; // 1232 bytes
+pub const MAX_TRANSACTION_ACCOUNTS: usize = 64; // Account locks, lookup table ones included
+
+#[derive(Debug)]
+pub struct InstructionFootprint {
+    pub program_id: Pubkey,
+    pub account_count: usize,
+    /// Accounts, program included, no earlier instruction of the transaction uses
+    pub new_accounts: Vec<Pubkey>,
+}
+
+/// Serialized size and accounts of a transaction, checked before sending it.
+#[derive(Debug)]
+pub struct TxSize {
+    pub size: usize,
+    pub account_count: usize,
+    /// Accounts passed by index into a lookup table
+    pub lookup_accounts: Vec<Pubkey>,
+    pub instructions: Vec<InstructionFootprint>,
+}
+
+impl TxSize {
+    pub fn new(
+        transaction: &VersionedTransaction,
+        instructions: &[Instruction],
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> TxSize {
+        let message = &transaction.message;
+
+        let mut lookup_accounts: Vec<Pubkey> = vec![];
+        for lookup in message.address_table_lookups().unwrap_or_default() {
+            let table = lookup_tables
+                .iter()
+                .find(|t| t.key == lookup.account_key)
+                .unwrap();
+            lookup_accounts.extend(
+                lookup
+                    .writable_indexes
+                    .iter()
+                    .chain(lookup.readonly_indexes.iter())
+                    .map(|i| table.addresses[*i as usize]),
+            );
+        }
+
+        let mut seen: Vec<Pubkey> = vec![];
+        let instructions = instructions
+            .iter()
+            .map(|ix| {
+                let mut new_accounts = vec![];
+                for account in std::iter::once(&ix.program_id)
+                    .chain(ix.accounts.iter().map(|meta| &meta.pubkey))
+                {
+                    if !seen.contains(account) {
+                        seen.push(*account);
+                        new_accounts.push(*account);
+                    }
+                }
+
+                InstructionFootprint {
+                    program_id: ix.program_id,
+                    account_count: ix.accounts.len(),
+                    new_accounts,
+                }
+            })
+            .collect();
+
+        TxSize {
+            size: bincode::serialize(transaction).unwrap().len(),
+            account_count: message.static_account_keys().len() + lookup_accounts.len(),
+            lookup_accounts,
+            instructions,
+        }
+    }
+
+    pub fn fits(&self) -> bool {
+        self.size <= MAX_TRANSACTION_SIZE && self.account_count <= MAX_TRANSACTION_ACCOUNTS
+    }
+
+    pub fn assert_fits(&self) {
+        assert!(self.fits(), "{}", self.breakdown());
+    }
+
+    /// Which instruction brought which accounts, those from lookup tables are marked.
+    pub fn breakdown(&self) -> String {
+        let mut breakdown = format!(
+            "transaction: {} bytes (max {}), {} accounts (max {})\n",
+            self.size, MAX_TRANSACTION_SIZE, self.account_count, MAX_TRANSACTION_ACCOUNTS
+        );
+        for (index, ix) in self.instructions.iter().enumerate() {
+            writeln!(
+                breakdown,
+                "  #{} {}: {} accounts, {} new",
+                index,
+                ix.program_id,
+                ix.account_count,
+                ix.new_accounts.len()
+            )
+            .unwrap();
+            for account in &ix.new_accounts {
+                let lookup = if self.lookup_accounts.contains(account) {
+                    " (lookup table)"
+                } else {
+                    ""
+                };
+                writeln!(breakdown, "      {}{}", account, lookup).unwrap();
+            }
+        }
+
+        breakdown
+    }
+}
@@ -1,5 +0,0 @@
-pub mod ratex_test_context;
-pub mod user_test_context;
-
-pub use ratex_test_context::*;
-pub use user_test_context::*;
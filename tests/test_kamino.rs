@@ -1,19 +1,87 @@
 #![cfg(test)]
+#![allow(clippy::await_holding_refcell_ref)]
 
-mod context;
-mod utilities;
-
-use context::{MintType, RateXTestContext};
-use solana_program_test::*;
-use utilities::kamino::{
-    dump_reserve, EXAMPLE_OBLIGATION, JITOSOL_MINT, MAIN_MARKET, RESERVE_JITOSOL_STATE,
-    RESERVE_SOL_STATE, RESERVE_USDC_STATE,
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use borsh::BorshDeserialize;
+use fixed::types::U68F60 as Fraction;
+use integrate_klend_2::accounts::{parse_account_dump, KaminoAccount};
+use integrate_klend_2::context::{
+    obligation_reserves, Deleverage, DeleverageTarget, LeverageError, LeverageStrategy,
+    LeverageTarget, Liquidator, PricePath, PricePathError, RateXTestContext, Rebalance,
+    RebalancePlan, UserTestContext,
+};
+use integrate_klend_2::decoder::{decode_instruction, decode_transaction, DecodeError};
+use integrate_klend_2::helper::{
+    check_transaction_size, dump_reserve, get_associated_token_address, process_instructions,
+    process_instructions_with_diff, process_v0_instructions, read_account_data,
+    try_process_instructions, ComputeBudget,
+};
+use integrate_klend_2::kamino::{
+    compose_klend_borrow_obligation_liquidity_ix, compose_klend_flash_borrow_ix,
+    compose_klend_flash_repay_ix, compose_klend_refresh_obligation_ix,
+    compose_klend_refresh_reserve_ix, lending_market_authority, referrer_short_url, referrer_state,
+    referrer_token_state, Asset, ALTCOINS_MARKET, DETACHED_FARMS, EXAMPLE_OBLIGATION, JITOSOL_MINT,
+    KAMINO_RESERVES, KAMINO_SCOPE_PRICES, KFARM_PROGRAM_ID, KLEND_PROGRAM_ID, MAIN_MARKET,
+    RESERVE_JITOSOL_STATE, RESERVE_JTO_STATE, RESERVE_SOL_STATE, RESERVE_USDC_STATE,
 };
+use integrate_klend_2::klend_math::{
+    fraction_from_bps, liquidity_to_collateral, market_price, simulate_accrual, slots_from_seconds,
+};
+use integrate_klend_2::lending_error::{assert_klend_error, LendingError};
+use integrate_klend_2::lookup_table::read_lookup_table;
+use integrate_klend_2::obligation_health::ObligationHealth;
+use integrate_klend_2::reserve_registry::{ReserveDataError, ReserveInfo, ReserveRegistry};
+use integrate_klend_2::reserve_snapshot::ReserveSnapshot;
+use integrate_klend_2::scope::{OraclePrices, ScopeError};
+use integrate_klend_2::tx_size::{MAX_TRANSACTION_ACCOUNTS, MAX_TRANSACTION_SIZE};
+use serde_json::json;
+use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::hash::hash;
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+use solana_program_test::*;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::compute_budget;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::signature::Signature;
+use solana_sdk::signature::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::VersionedTransaction;
+use std::time::Duration;
+
+/// Moves past the slot the USDC reserve fixture was last updated at, 298271854.
+fn warp_past_usdc_fixture(rtc: &RateXTestContext) {
+    rtc.context.borrow_mut().warp_to_slot(298271855).unwrap();
+}
+
+/// Opens a 1.5x JitoSOL/SOL position for the user in the main market, from 10 JitoSOL.
+async fn open_leveraged_position(rtc: &RateXTestContext, user: &UserTestContext) -> Pubkey {
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    user.klend_init_user_metadata().await;
+
+    let obligation = main_market.init_obligation(&user.user, 0, 0).await;
+    rtc.mint_asset(user, Asset::JitoSol, 10_000_000_000).await;
+    LeverageStrategy {
+        collateral: Asset::JitoSol,
+        debt: Asset::Sol,
+        initial_deposit: 10_000_000_000,
+        target: LeverageTarget::Leverage(Fraction::from_num(1.5)),
+    }
+    .execute(&main_market, &user.user, &obligation)
+    .await;
+
+    obligation
+}
 
 #[tokio::test]
 async fn test_kamino() {
     let rtc = RateXTestContext::new().await;
     let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
 
     rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
 
@@ -21,44 +89,179 @@ async fn test_kamino() {
     // dump_reserve(&RESERVE_USDC_STATE);
 
     alice.klend_init_user_metadata().await;
-    let obligation = alice.klend_init_obligation(&MAIN_MARKET, 0, 0).await;
-    alice.klend_refresh_reserve(&RESERVE_JITOSOL_STATE).await;
-    alice.klend_refresh_obligation(&obligation, &vec![]).await;
-
-    rtc.mint_token_by_type(alice, 50_000_000_000, MintType::JitoSol)
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+    let refresh = process_instructions_with_diff(
+        &mut rtc.context.borrow_mut(),
+        &alice.user,
+        &[compose_klend_refresh_reserve_ix(
+            rtc.registry.get(&RESERVE_JITOSOL_STATE),
+        )],
+        &[RESERVE_JITOSOL_STATE],
+    )
+    .await;
+    let refreshed = refresh
+        .state_diff
+        .unwrap()
+        .field(&RESERVE_JITOSOL_STATE, "last_update.slot")
+        .cloned()
+        .unwrap();
+    assert!(refreshed.after > refreshed.before);
+    main_market
+        .refresh_obligation(&alice.user, &obligation)
         .await;
+
+    rtc.mint_asset(alice, Asset::JitoSol, 50_000_000_000).await;
     alice
         .assert_mint_balance(JITOSOL_MINT, 50_000_000_000)
         .await;
 
-    alice
-        .klend_deposit_reserve_jitosol_liquidity(50_000_000_000)
+    main_market
+        .deposit_reserve_liquidity(&alice.user, Asset::JitoSol, 50_000_000_000)
         .await;
 
-    alice.klend_deposit_obligation_collateral(&obligation).await;
+    main_market
+        .deposit_obligation_collateral(&alice.user, &obligation, Asset::JitoSol)
+        .await;
 
-    println!(
-        "user sol balance before borrowing: {}",
-        alice.balance().await
-    );
-    alice
-        .klend_borrow_obligation_liquidity(&obligation, "SOL", 20_000_000_000)
+    let sol_vault = main_market.reserve(Asset::Sol).liquidity_supply_vault;
+    let watched = [
+        alice.user.pubkey(),
+        obligation,
+        RESERVE_SOL_STATE,
+        sol_vault,
+    ];
+
+    let before = rtc.snapshot(&watched).await;
+    main_market
+        .borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, 20_000_000_000)
         .await;
-    println!(
-        "user sol balance after borrowing: {}",
-        alice.balance().await
+    let borrowed = before.diff(&rtc.snapshot(&watched).await);
+    println!("{}", borrowed.to_table());
+    let sol_borrow = format!("borrows[{}].borrowed_amount_sf", RESERVE_SOL_STATE);
+
+    // The native SOL is unwrapped, minus the transaction fees
+    let received = borrowed.account(&alice.user.pubkey()).lamport_delta();
+    assert!(received <= 20_000_000_000 && received > 20_000_000_000 - 100_000);
+    assert_eq!(borrowed.account(&sol_vault).token_delta(), -20_000_000_000);
+    assert_eq!(
+        borrowed
+            .field(&RESERVE_SOL_STATE, "liquidity.available_amount")
+            .unwrap()
+            .delta(),
+        -20_000_000_000.0
+    );
+    assert_eq!(
+        borrowed.field(&obligation, &sol_borrow).unwrap().after,
+        Fraction::from(20_000_000_000u64)
+    );
+    assert_eq!(
+        borrowed.field(&obligation, "has_debt").unwrap().after,
+        Fraction::ONE
     );
 
-    alice
-        .klend_repay_obligation_liquidity(&obligation, "SOL", 20_000_000_000)
+    let before = rtc.snapshot(&watched).await;
+    main_market
+        .repay_obligation_liquidity(&alice.user, &obligation, Asset::Sol, 20_000_000_000)
         .await;
-    println!("user sol balance after repaying: {}", alice.balance().await);
+    let repaid = before.diff(&rtc.snapshot(&watched).await);
+    println!("{}", repaid.to_table());
 
+    assert!(repaid.account(&alice.user.pubkey()).lamport_delta() < -20_000_000_000);
+    assert_eq!(repaid.account(&sol_vault).token_delta(), 20_000_000_000);
+    // The interest accrued in between stays owed
+    let remaining_debt = repaid.field(&obligation, &sol_borrow).unwrap().after;
+    assert!(remaining_debt > Fraction::ZERO && remaining_debt < Fraction::from(1_000u64));
+
+    main_market
+        .withdraw_obligation_collateral(&alice.user, &obligation, Asset::JitoSol, u64::MAX)
+        .await;
+
+    main_market
+        .redeem_reserve_collateral(&alice.user, Asset::JitoSol)
+        .await;
+}
+
+#[tokio::test]
+async fn test_altcoins_market() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let altcoins_market = rtc.lending_market_client(&ALTCOINS_MARKET);
+    assert_eq!(altcoins_market.reserves(), vec![RESERVE_JTO_STATE]);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+
+    rtc.mint_asset(alice, Asset::Jto, 1_100_000_000_000).await;
+
+    alice.klend_init_user_metadata().await;
+    let obligation = altcoins_market.init_obligation(&alice.user, 0, 0).await;
+
+    altcoins_market
+        .deposit_reserve_liquidity(&alice.user, Asset::Jto, 1_000_000_000_000)
+        .await;
+    altcoins_market
+        .deposit_obligation_collateral(&alice.user, &obligation, Asset::Jto)
+        .await;
+
+    altcoins_market
+        .borrow_obligation_liquidity(&alice.user, &obligation, Asset::Jto, 10_000_000_000)
+        .await;
     alice
-        .klend_withdraw_obligation_collateral(&obligation)
+        .assert_mint_balance(
+            altcoins_market.reserve(Asset::Jto).liquidity_mint,
+            110_000_000_000,
+        )
+        .await;
+
+    altcoins_market
+        .repay_obligation_liquidity(&alice.user, &obligation, Asset::Jto, u64::MAX)
+        .await;
+    let obligation_state = altcoins_market.get_obligation(&obligation).await;
+    assert_eq!(obligation_state.borrows[0].borrowed_amount_sf, 0);
+
+    altcoins_market
+        .withdraw_obligation_collateral(&alice.user, &obligation, Asset::Jto, u64::MAX)
+        .await;
+    altcoins_market
+        .redeem_reserve_collateral(&alice.user, Asset::Jto)
+        .await;
+}
+
+#[tokio::test]
+async fn test_usdc_collateral() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    warp_past_usdc_fixture(&rtc);
+
+    rtc.mint_asset(alice, Asset::Usdc, 10_000_000_000).await;
+
+    alice.klend_init_user_metadata().await;
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+
+    main_market
+        .deposit_reserve_liquidity(&alice.user, Asset::Usdc, 10_000_000_000)
+        .await;
+    main_market
+        .deposit_obligation_collateral(&alice.user, &obligation, Asset::Usdc)
         .await;
 
-    alice.klend_redeem_reserve_jitosol_collateral().await;
+    let balance = alice.balance().await;
+    main_market
+        .borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, 10_000_000_000)
+        .await;
+    assert!(alice.balance().await > balance + 9_000_000_000);
+
+    main_market
+        .repay_obligation_liquidity(&alice.user, &obligation, Asset::Sol, 10_100_000_000)
+        .await;
+    main_market
+        .withdraw_obligation_collateral(&alice.user, &obligation, Asset::Usdc, u64::MAX)
+        .await;
+    main_market
+        .redeem_reserve_collateral(&alice.user, Asset::Usdc)
+        .await;
 }
 
 #[tokio::test]
@@ -66,8 +269,7 @@ async fn test_mock_swap_sol_to_jitosol() {
     let rtc = RateXTestContext::new().await;
     let admin = &rtc.users[0];
 
-    rtc.mint_token_by_type(admin, 8_000_000_000_000, MintType::Sol)
-        .await;
+    rtc.mint_asset(admin, Asset::Sol, 8_000_000_000_000).await;
 
     println!("admin sol balance before swap: {}", admin.balance().await);
     admin.mock_swap_sol_to_jitosol(8_000_000_000_000).await;
@@ -83,15 +285,982 @@ async fn test_leverage_borrow() {
 
     rtc.set_sysvar_clock(1730163565).await;
     admin.klend_init_user_metadata().await;
-    let obligation = admin.klend_init_obligation(&MAIN_MARKET, 0, 0).await;
-
-    rtc.mint_token_by_type(admin, 35_000_000_000, MintType::JitoSol)
+    let obligation = rtc
+        .lending_market_client(&MAIN_MARKET)
+        .init_obligation(&admin.user, 0, 0)
         .await;
 
-    // 51.6 jitosol， borrow 20 sol
-    admin.enter_leverage_borrow(&obligation).await;
+    rtc.mint_asset(admin, Asset::JitoSol, 35_000_000_000).await;
+
+    // 35 jitosol + 20 sol swapped to jitosol as collateral, borrow 20 sol
+    let report = admin
+        .enter_leverage_borrow(&obligation, 20_000_000_000)
+        .await;
+    println!(
+        "leverage tx: {} / {} CU {:?}",
+        report.compute_units_consumed, report.compute_unit_limit, report.instruction_compute_units
+    );
+    // The compute budget instructions followed by 12 instructions
+    assert_eq!(report.instruction_compute_units.len(), 14);
+    assert_eq!(report.instruction_compute_units[..2], [0, 0]);
+    assert!(report.instruction_compute_units[2..]
+        .iter()
+        .all(|units| *units > 0));
+    // Builtins, compute budget included, are charged without reporting it
+    assert!(report.instruction_compute_units.iter().sum::<u64>() <= report.compute_units_consumed);
+    // Sized from a simulation with a 10% margin
+    assert!(report.compute_units_consumed <= report.compute_unit_limit as u64);
+    assert!(report.compute_unit_limit as u64 <= report.compute_units_consumed * 12 / 10);
+    assert!(report.writable_accounts.contains(&obligation));
+    let jitosol = rtc.registry.asset(Asset::JitoSol);
+    admin.assert_mint_balance(jitosol.liquidity_mint, 0).await;
+    admin.assert_mint_balance(jitosol.collateral_mint, 0).await; // every collateral token deposited
 
     admin.leave_leverage_borrow(&obligation).await;
+    admin.assert_mint_balance(jitosol.collateral_mint, 0).await;
+    assert!(
+        !rtc.lending_market_client(&MAIN_MARKET)
+            .obligation_exists(&obligation)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_leverage_strategy() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    warp_past_usdc_fixture(&rtc);
+    admin.klend_init_user_metadata().await;
+
+    // JitoSOL LTV is 45%, 3x needs 66%
+    let jitosol_sol = |target| LeverageStrategy {
+        collateral: Asset::JitoSol,
+        debt: Asset::Sol,
+        initial_deposit: 10_000_000_000,
+        target,
+    };
+    let jitosol = main_market.get_reserve(Asset::JitoSol).await;
+    let sol = main_market.get_reserve(Asset::Sol).await;
+    assert!(matches!(
+        jitosol_sol(LeverageTarget::Leverage(Fraction::from_num(3))).plan(&jitosol, &sol, None),
+        Err(LeverageError::ExceedsLoanToValue { .. })
+    ));
+    // Nothing to lever, or an LTV that can't be reached at any leverage
+    assert!(matches!(
+        LeverageStrategy {
+            initial_deposit: 0,
+            ..jitosol_sol(LeverageTarget::Leverage(Fraction::from_num(1.5)))
+        }
+        .plan(&jitosol, &sol, None),
+        Err(LeverageError::InvalidTarget)
+    ));
+    assert!(matches!(
+        jitosol_sol(LeverageTarget::Ltv(Fraction::ONE)).plan(&jitosol, &sol, None),
+        Err(LeverageError::InvalidTarget)
+    ));
+    assert!(matches!(
+        jitosol_sol(LeverageTarget::Ltv(Fraction::from_num(1.5))).plan(&jitosol, &sol, None),
+        Err(LeverageError::InvalidTarget)
+    ));
+    // Within the LTV of the JitoSOL / SOL elevation group
+    let market = main_market.get_lending_market().await;
+    let group = market
+        .elevation_groups
+        .iter()
+        .find(|group| group.id != 0 && group.debt_reserve == RESERVE_SOL_STATE);
+    assert!(jitosol_sol(LeverageTarget::Leverage(Fraction::from_num(3)))
+        .plan(&jitosol, &sol, group)
+        .is_ok());
+
+    let obligation = main_market.init_obligation(&admin.user, 0, 0).await;
+    rtc.mint_asset(admin, Asset::JitoSol, 10_000_000_000).await;
+    let plan = jitosol_sol(LeverageTarget::Leverage(Fraction::from_num(1.5)))
+        .execute(&main_market, &admin.user, &obligation)
+        .await;
+    assert!(plan.ltv < Fraction::from_num(0.42)); // 1/3 with the SOL borrow factor of 125%
+
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let state = main_market.get_obligation(&obligation).await;
+    let deposited = Fraction::from_bits(state.deposited_value_sf);
+    let borrowed = Fraction::from_bits(state.borrowed_assets_market_value_sf);
+    let leverage = deposited / (deposited - borrowed);
+    assert!(leverage > Fraction::from_num(1.49) && leverage < Fraction::from_num(1.51));
+
+    // Any other pair, sized by a target LTV, added to the same obligation
+    let sol_debt = Fraction::from_bits(state.borrows[0].borrowed_amount_sf);
+    rtc.mint_asset(admin, Asset::Usdc, 1_000_000_000).await;
+    let plan = LeverageStrategy {
+        collateral: Asset::Usdc,
+        debt: Asset::Sol,
+        initial_deposit: 1_000_000_000,
+        target: LeverageTarget::Ltv(Fraction::from_num(0.5)),
+    }
+    .execute(&main_market, &admin.user, &obligation)
+    .await;
+    assert_eq!(plan.deposit_amount, 1_000_000_000 + plan.swap_output);
+
+    let state = main_market.get_obligation(&obligation).await;
+    assert_eq!(
+        state.deposits[1].deposit_reserve,
+        main_market.reserve(Asset::Usdc).address
+    );
+    let borrowed = Fraction::from_bits(state.borrows[0].borrowed_amount_sf) - sol_debt;
+    // Only interest accrued on top of the planned borrow
+    assert!(borrowed - Fraction::from(plan.borrow_amount) < Fraction::from_num(1_000_000));
+}
+
+#[tokio::test]
+async fn test_deleverage_strategy() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    let obligation = open_leveraged_position(&rtc, admin).await;
+
+    let obligation_ltv = || async {
+        main_market
+            .refresh_obligation(&admin.user, &obligation)
+            .await;
+        let state = main_market.get_obligation(&obligation).await;
+        Fraction::from_bits(state.borrow_factor_adjusted_debt_value_sf)
+            / Fraction::from_bits(state.deposited_value_sf)
+    };
+    let tolerance = Fraction::from_num(0.005);
+
+    // An obligation without deposited value can't be planned
+    let mut worthless = main_market.get_obligation(&obligation).await;
+    worthless.deposited_value_sf = 0;
+    let deleverage = Deleverage {
+        collateral: Asset::JitoSol,
+        debt: Asset::Sol,
+        target: DeleverageTarget::Ltv(Fraction::from_num(0.1)),
+    };
+    assert!(matches!(
+        deleverage.plan(
+            &main_market.get_reserve(Asset::JitoSol).await,
+            &main_market.get_reserve(Asset::Sol).await,
+            &main_market.reserve(Asset::JitoSol).address,
+            &main_market.reserve(Asset::Sol).address,
+            &worthless,
+            None,
+        ),
+        Err(LeverageError::InvalidTarget)
+    ));
+
+    // The mock swap pays the SOL out of the user wallet
+    rtc.mint_asset(admin, Asset::Sol, 10_000_000_000).await;
+
+    // Repay 1 SOL out of the collateral
+    let debt_before = main_market.get_obligation(&obligation).await.borrows[0].borrowed_amount_sf;
+    let plan = Deleverage {
+        collateral: Asset::JitoSol,
+        debt: Asset::Sol,
+        target: DeleverageTarget::Repay(1_000_000_000),
+    }
+    .execute(&main_market, &admin.user, &obligation)
+    .await;
+    assert_eq!(plan.repay_amount, 1_000_000_000);
+    let state = main_market.get_obligation(&obligation).await;
+    let repaid =
+        Fraction::from_bits(debt_before) - Fraction::from_bits(state.borrows[0].borrowed_amount_sf);
+    // Only interest accrued on top of the planned repay
+    assert!(Fraction::from(plan.repay_amount) - repaid < Fraction::from_num(1_000_000));
+
+    // Down to 30%, the position stays open
+    let rebalance = |target_ltv| Rebalance {
+        collateral: Asset::JitoSol,
+        debt: Asset::Sol,
+        target_ltv,
+    };
+    let plan = rebalance(Fraction::from_num(0.3))
+        .execute(&main_market, &admin.user, &obligation)
+        .await;
+    assert!(matches!(plan, RebalancePlan::Deleverage(_)));
+    assert!(obligation_ltv().await.abs_diff(Fraction::from_num(0.3)) < tolerance);
+
+    // And back up to 40%
+    let plan = rebalance(Fraction::from_num(0.4))
+        .execute(&main_market, &admin.user, &obligation)
+        .await;
+    assert!(matches!(plan, RebalancePlan::Leverage(_)));
+    let ltv = obligation_ltv().await;
+    assert!(ltv.abs_diff(Fraction::from_num(0.4)) < tolerance);
+
+    // Already there, no transaction
+    let jitosol = main_market.get_reserve(Asset::JitoSol).await;
+    let sol = main_market.get_reserve(Asset::Sol).await;
+    assert!(matches!(
+        rebalance(ltv).plan(
+            &jitosol,
+            &sol,
+            &main_market.reserve(Asset::JitoSol).address,
+            &main_market.reserve(Asset::Sol).address,
+            &main_market.get_obligation(&obligation).await,
+            None,
+        ),
+        Ok(RebalancePlan::Unchanged)
+    ));
+
+    // Repay everything, the SOL borrow is closed
+    rtc.mint_asset(admin, Asset::Sol, 10_000_000_000).await;
+    let plan = Deleverage {
+        collateral: Asset::JitoSol,
+        debt: Asset::Sol,
+        target: DeleverageTarget::Repay(u64::MAX),
+    }
+    .execute(&main_market, &admin.user, &obligation)
+    .await;
+    assert_eq!(plan.ltv, Fraction::ZERO);
+    let state = main_market.get_obligation(&obligation).await;
+    assert_eq!(state.borrows[0].borrowed_amount_sf, 0);
+    assert!(state.deposits[0].deposited_amount > 0);
+}
+
+#[tokio::test]
+async fn test_obligation_health() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let jitosol = main_market.reserve(Asset::JitoSol).address;
+    let sol = main_market.reserve(Asset::Sol).address;
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    let obligation = open_leveraged_position(&rtc, admin).await;
+
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let state = main_market.get_obligation(&obligation).await;
+    let health = main_market.obligation_health(&obligation).await;
+    let tolerance = Fraction::from_num(0.0001);
+
+    // Same values as refresh_obligation
+    let deposited = Fraction::from_bits(state.deposited_value_sf);
+    assert!(health.deposited_value.abs_diff(deposited) / deposited < tolerance);
+    let ltv = Fraction::from_bits(state.borrow_factor_adjusted_debt_value_sf) / deposited;
+    assert!(health.ltv.abs_diff(ltv) < tolerance);
+    assert!(health.max_ltv.abs_diff(Fraction::from_num(0.45)) < tolerance);
+    assert!(health.liquidation_ltv.abs_diff(Fraction::from_num(0.55)) < tolerance);
+    assert!(!health.is_liquidatable());
+
+    // JitoSOL is the only collateral: liquidatable once its price drops by ltv / liquidation ltv
+    let price = health.liquidation_prices[0].price;
+    let liquidation_price = health.liquidation_price(&jitosol).unwrap();
+    assert!(liquidation_price.abs_diff(price * health.ltv / health.liquidation_ltv) < tolerance);
+
+    // Borrowing the whole capacity brings the obligation to its max LTV
+    let capacity = health.borrow_capacity(&sol);
+    main_market
+        .borrow_obligation_liquidity(&admin.user, &obligation, Asset::Sol, capacity)
+        .await;
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let health = main_market.obligation_health(&obligation).await;
+    assert!(health.ltv.abs_diff(health.max_ltv) < tolerance);
+    assert!(health.borrow_capacity(&sol) < 1_000_000);
+}
+
+#[tokio::test]
+async fn test_reserve_snapshot() {
+    let sol = ReserveSnapshot::from_fixture(&RESERVE_SOL_STATE);
+    assert!(sol.utilization_rate > Fraction::ZERO && sol.utilization_rate < Fraction::ONE);
+    assert!(sol.supply_apr < sol.borrow_apr * sol.utilization_rate);
+    assert!(sol.borrow_apy > sol.borrow_apr.to_num::<f64>());
+
+    let curve = sol.rate_curve(10);
+    assert_eq!(curve.len(), 11);
+    assert_eq!(curve[0].supply_apy, 0.0);
+    assert!(curve.windows(2).all(|p| p[0].borrow_apy <= p[1].borrow_apy));
+
+    // Predicts the collateral minted for a deposit
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    rtc.mint_asset(admin, Asset::JitoSol, 10_000_000_000).await;
+
+    let jitosol = ReserveSnapshot::new(&main_market.get_reserve(Asset::JitoSol).await);
+    main_market
+        .deposit_reserve_liquidity(&admin.user, Asset::JitoSol, 10_000_000_000)
+        .await;
+    admin
+        .assert_mint_balance(
+            main_market.reserve(Asset::JitoSol).collateral_mint,
+            jitosol.liquidity_to_collateral(10_000_000_000),
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn test_reserve_accrual() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let referral_fee_bps = main_market.get_lending_market().await.referral_fee_bps;
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    main_market.refresh_reserve(&admin.user, Asset::Sol).await;
+    let sol = main_market.get_reserve_account(Asset::Sol).await;
+
+    // A week later, in a single refresh
+    let slots = slots_from_seconds(7 * 24 * 3600);
+    rtc.context
+        .borrow_mut()
+        .warp_to_slot(sol.last_update.slot + slots)
+        .unwrap();
+    main_market.refresh_reserve(&admin.user, Asset::Sol).await;
+
+    let actual = main_market.get_reserve_account(Asset::Sol).await;
+    let expected = simulate_accrual(&sol, slots, referral_fee_bps);
+    assert_eq!(actual.last_update.slot, expected.last_update.slot);
+    assert_eq!(
+        actual.liquidity.cumulative_borrow_rate_bsf.value,
+        expected.liquidity.cumulative_borrow_rate_bsf.value
+    );
+    assert_eq!(
+        actual.liquidity.borrowed_amount_sf,
+        expected.liquidity.borrowed_amount_sf
+    );
+    assert_eq!(
+        actual.liquidity.accumulated_protocol_fees_sf,
+        expected.liquidity.accumulated_protocol_fees_sf
+    );
+    assert_eq!(
+        actual.liquidity.pending_referrer_fees_sf,
+        expected.liquidity.pending_referrer_fees_sf
+    );
+}
+
+#[tokio::test]
+async fn test_advance_time() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    let obligation = open_leveraged_position(&rtc, admin).await;
+
+    let sol_apr = ReserveSnapshot::new(&main_market.get_reserve(Asset::Sol).await).borrow_apr;
+    let debt = || async {
+        let state = main_market.get_obligation(&obligation).await;
+        Fraction::from_bits(state.borrows[0].borrowed_amount_sf)
+    };
+    let start_debt = debt().await;
+    let start = rtc.get_clock().await;
+
+    let mut ltv = main_market.obligation_health(&obligation).await.ltv;
+    for days in [30, 90, 365] {
+        let elapsed = (rtc.get_clock().await.unix_timestamp - start.unix_timestamp) as u64;
+        rtc.advance_time(Duration::from_secs(days * 24 * 3600 - elapsed))
+            .await;
+
+        // Interest accrued at about the SOL borrow rate, prices stayed fresh
+        let growth = debt().await / start_debt - Fraction::ONE;
+        let expected = sol_apr * days as u128 / 365;
+        assert!(growth > expected && growth < expected * Fraction::from_num(1.1));
+
+        let health = main_market.obligation_health(&obligation).await;
+        assert!(health.ltv > ltv);
+        ltv = health.ltv;
+    }
+}
+
+#[tokio::test]
+async fn test_scope_price_override() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let jitosol = main_market.reserve(Asset::JitoSol).address;
+
+    assert_eq!(
+        OraclePrices::from_account_data(&[0; 40]).err(),
+        Some(ScopeError::InvalidAccountLength {
+            len: 40,
+            expected: 40 + 512 * 56
+        })
+    );
+
+    let mut prices = rtc.get_scope_prices().await;
+    let index = main_market.reserve(Asset::JitoSol).scope_price_chain[0];
+    assert_eq!(
+        prices.set_price(index, Fraction::MAX, 0, 0),
+        Err(ScopeError::PriceOverflow { index })
+    );
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    let obligation = open_leveraged_position(&rtc, admin).await;
+
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let liquidation_price = main_market
+        .obligation_health(&obligation)
+        .await
+        .liquidation_price(&jitosol)
+        .unwrap();
+
+    // JitoSOL depegs, just above the liquidation price
+    let price = liquidation_price * Fraction::from_num(1.01);
+    rtc.set_asset_price(Asset::JitoSol, price).await;
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let reserve_price = market_price(&main_market.get_reserve_account(Asset::JitoSol).await);
+    assert!(reserve_price.abs_diff(price) < Fraction::from_num(0.0001));
+    assert!(!main_market
+        .obligation_health(&obligation)
+        .await
+        .is_liquidatable());
+
+    // And just below
+    rtc.set_asset_price(Asset::JitoSol, liquidation_price * Fraction::from_num(0.99))
+        .await;
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let state = main_market.get_obligation(&obligation).await;
+    assert!(state.borrow_factor_adjusted_debt_value_sf > state.unhealthy_borrow_value_sf);
+    assert!(main_market
+        .obligation_health(&obligation)
+        .await
+        .is_liquidatable());
+}
+
+#[tokio::test]
+async fn test_price_replay() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    warp_past_usdc_fixture(&rtc);
+    let obligation = open_leveraged_position(&rtc, admin).await;
+
+    // Daily jitoSOL/SOL rates (scope entry 51), depegging down to 0.86
+    let path = PricePath::from_fixture("jitosol_sol_depeg.csv", &rtc.registry);
+    assert_eq!(path.points.len(), 7);
+    let from_csv = |csv| PricePath::from_csv(csv, &rtc.registry).map(|_| ());
+    assert_eq!(
+        from_csv("1717200000,51"),
+        Err(PricePathError::InvalidRow("1717200000,51".to_string()))
+    );
+    assert_eq!(
+        from_csv("1717200000,BONK,1.0"),
+        Err(PricePathError::UnknownAsset("BONK".to_string()))
+    );
+    assert_eq!(
+        from_csv("1717286400,51,1.0\n1717200000,51,1.0"),
+        Err(PricePathError::Unsorted)
+    );
+
+    let report = path.replay(&rtc, &[obligation]).await;
+    println!("{}", report.to_table());
+
+    assert_eq!(report.rows.len(), 7);
+    assert!(report.rows.windows(2).take(5).all(|r| r[0].ltv < r[1].ltv));
+    // 1.5x is liquidatable below a rate of about 0.87
+    assert_eq!(report.first_liquidation(&obligation), Some(1717632000));
+    assert!(!report.rows[6].liquidatable);
+}
+
+#[tokio::test]
+async fn test_liquidation() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let liquidator = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let jitosol = main_market.reserve(Asset::JitoSol).address;
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    let obligation = open_leveraged_position(&rtc, admin).await;
+
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let liquidation_price = main_market
+        .obligation_health(&obligation)
+        .await
+        .liquidation_price(&jitosol)
+        .unwrap();
+    rtc.set_asset_price(Asset::JitoSol, liquidation_price * Fraction::from_num(0.95))
+        .await;
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let before = main_market.obligation_health(&obligation).await;
+    assert!(before.is_liquidatable());
+
+    // Try to repay the whole debt, klend caps it with the close factor
+    let state = main_market.get_obligation(&obligation).await;
+    let debt: u64 = Fraction::from_bits(state.borrows[0].borrowed_amount_sf)
+        .ceil()
+        .to_num();
+    rtc.mint_asset(liquidator, Asset::Sol, 2 * debt).await;
+    let outcome = liquidator
+        .klend_liquidate(&main_market, &obligation, Asset::Sol, Asset::JitoSol, debt)
+        .await;
+    println!("{:?}", outcome);
+
+    let close_factor = main_market
+        .get_lending_market()
+        .await
+        .liquidation_max_debt_close_factor_pct;
+    assert!(outcome.repaid_amount < debt * close_factor as u64 / 100 + 1_000_000);
+
+    // The bonus comes out of the liquidated obligation
+    let config = main_market.get_reserve(Asset::JitoSol).await.config;
+    let bonus = outcome.bonus.unwrap();
+    assert!(bonus > Fraction::ZERO);
+    assert!(bonus <= fraction_from_bps(config.max_liquidation_bonus_bps as u64));
+
+    main_market
+        .refresh_obligation(&admin.user, &obligation)
+        .await;
+    let after = main_market.obligation_health(&obligation).await;
+    let net_value = |h: &ObligationHealth| h.deposited_value - h.borrowed_value;
+    let loss = net_value(&before) - net_value(&after);
+    assert!(
+        loss.abs_diff(outcome.withdrawn_value - outcome.repaid_value) < Fraction::from_num(0.01)
+    );
+    // Part of the bonus goes to the protocol
+    assert!(outcome.received_value < outcome.withdrawn_value);
+    assert!(after.ltv < before.ltv);
+}
+
+#[tokio::test]
+async fn test_liquidator() {
+    let rtc = RateXTestContext::new().await;
+    let admin = &rtc.users[0];
+    let alice = &rtc.users[1];
+    let liquidator = &rtc.users[2];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let jitosol = main_market.reserve(Asset::JitoSol).address;
+    let sol = main_market.reserve(Asset::Sol).address;
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    warp_past_usdc_fixture(&rtc);
+
+    // Vault position at 1.5x
+    let vault = open_leveraged_position(&rtc, admin).await;
+
+    // Riskier position borrowing close to the max LTV
+    alice.klend_init_user_metadata().await;
+    let risky = main_market.init_obligation(&alice.user, 0, 0).await;
+    rtc.mint_asset(alice, Asset::JitoSol, 10_000_000_000).await;
+    main_market
+        .deposit_reserve_liquidity(&alice.user, Asset::JitoSol, 10_000_000_000)
+        .await;
+    main_market
+        .deposit_obligation_collateral(&alice.user, &risky, Asset::JitoSol)
+        .await;
+    main_market.refresh_obligation(&alice.user, &risky).await;
+    let capacity = main_market
+        .obligation_health(&risky)
+        .await
+        .borrow_capacity(&sol);
+    main_market
+        .borrow_obligation_liquidity(&alice.user, &risky, Asset::Sol, capacity * 98 / 100)
+        .await;
+
+    assert_eq!(rtc.obligations().await, vec![vault, risky]);
+
+    let liquidator_bot = Liquidator {
+        flash_loan: true,
+        max_liquidations: 10,
+    };
+    rtc.mint_asset(liquidator, Asset::Sol, 20_000_000_000).await;
+    assert!(liquidator_bot.run(&rtc, liquidator).await.is_empty());
+
+    // Shock the jitoSOL price until the vault gets liquidated
+    let price = main_market.get_reserve(Asset::JitoSol).await;
+    let price = market_price(&price);
+    let mut first_liquidated: Vec<Pubkey> = vec![];
+    for shock in [0.9, 0.85, 0.8, 0.78, 0.75, 0.7] {
+        rtc.set_asset_price(Asset::JitoSol, price * Fraction::from_num(shock))
+            .await;
+        let reports = liquidator_bot.run(&rtc, liquidator).await;
+        println!("shock {}: {} liquidations", shock, reports.len());
+
+        for report in &reports {
+            assert_eq!(report.candidate.repay_reserve, sol);
+            assert_eq!(report.candidate.withdraw_reserve, jitosol);
+            assert!(report.outcome.repaid_amount <= report.candidate.repay_amount);
+            assert!(report.outcome.bonus.unwrap() > Fraction::ZERO);
+            if !first_liquidated.contains(&report.candidate.obligation) {
+                first_liquidated.push(report.candidate.obligation);
+            }
+        }
+        for obligation in rtc.obligations().await {
+            assert!(!main_market
+                .obligation_health(&obligation)
+                .await
+                .is_liquidatable());
+        }
+    }
+
+    assert_eq!(first_liquidated, vec![risky, vault]);
+    // The liquidator keeps part of the bonus in jitoSOL
+    assert!(liquidator.mint_balance(&JITOSOL_MINT).await > 0);
+}
+
+#[tokio::test]
+async fn test_klend_errors() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let sol = main_market.reserve(Asset::Sol).address;
+
+    assert_eq!(
+        LendingError::from_code(6007),
+        Some(LendingError::MathOverflow)
+    );
+    assert_eq!(
+        LendingError::CannotCalculateReferralAmountDueToSlotsMismatch.code(),
+        6111
+    );
+    assert_eq!(LendingError::from_code(6112), None);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+
+    alice.klend_init_user_metadata().await;
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+    rtc.mint_asset(alice, Asset::JitoSol, 10_000_000_000).await;
+    main_market
+        .deposit_reserve_liquidity(&alice.user, Asset::JitoSol, 10_000_000_000)
+        .await;
+    main_market
+        .deposit_obligation_collateral(&alice.user, &obligation, Asset::JitoSol)
+        .await;
+    main_market
+        .refresh_obligation(&alice.user, &obligation)
+        .await;
+
+    // Borrow above the max LTV
+    let capacity = main_market
+        .obligation_health(&obligation)
+        .await
+        .borrow_capacity(&sol);
+    let result = main_market
+        .try_borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, capacity * 11 / 10)
+        .await;
+    assert_klend_error(result, LendingError::BorrowTooLarge);
+
+    // Withdraw leaving the obligation above its max LTV
+    main_market
+        .borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, capacity * 9 / 10)
+        .await;
+    let result = main_market
+        .try_withdraw_obligation_collateral(&alice.user, &obligation, Asset::JitoSol, 1_000_000_000)
+        .await;
+    assert_klend_error(result, LendingError::WithdrawTooLarge);
+
+    // Flash repay less than borrowed
+    let jitosol = main_market.reserve(Asset::JitoSol);
+    let user_liquidity = get_associated_token_address(&alice.user.pubkey(), &JITOSOL_MINT).await;
+    let instructions = vec![
+        compose_klend_flash_borrow_ix(
+            &alice.user.pubkey(),
+            &main_market.market,
+            &main_market.market_authority,
+            &jitosol.address,
+            &jitosol.liquidity_mint,
+            &jitosol.liquidity_supply_vault,
+            &user_liquidity,
+            &jitosol.liquidity_fee_vault,
+            1_000_000_000,
+        ),
+        compose_klend_flash_repay_ix(
+            &alice.user.pubkey(),
+            &main_market.market,
+            &main_market.market_authority,
+            &jitosol.address,
+            &jitosol.liquidity_mint,
+            &jitosol.liquidity_supply_vault,
+            &user_liquidity,
+            &jitosol.liquidity_fee_vault,
+            999_999_999,
+            ComputeBudget::default().instruction_count() as u8,
+        ),
+    ];
+    let result =
+        try_process_instructions(&mut rtc.context.borrow_mut(), &alice.user, &instructions).await;
+    assert_klend_error(result, LendingError::InvalidFlashRepay);
+
+    // Oracle not updated for a day
+    let clock = rtc.get_clock().await;
+    let price = rtc.get_scope_prices().await.price(0);
+    rtc.set_scope_price(0, price, clock.unix_timestamp - 86_400)
+        .await;
+    // klend refreshes the reserve without its price, borrowing then finds it stale
+    main_market.refresh_reserve(&alice.user, Asset::Sol).await;
+    let result = main_market
+        .try_borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, 1_000_000)
+        .await;
+    assert_klend_error(result, LendingError::ReserveStale);
+}
+
+#[tokio::test]
+async fn test_lookup_table() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+    warp_past_usdc_fixture(&rtc);
+
+    let table = alice.klend_init_user_metadata().await;
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+    rtc.mint_asset(alice, Asset::JitoSol, 10_000_000_000).await;
+    main_market
+        .deposit_reserve_liquidity(&alice.user, Asset::JitoSol, 10_000_000_000)
+        .await;
+    main_market
+        .deposit_obligation_collateral(&alice.user, &obligation, Asset::JitoSol)
+        .await;
+
+    main_market.extend_lookup_table(&alice.user, &table).await;
+    // Extending again only adds what is missing
+    main_market.extend_lookup_table(&alice.user, &table).await;
+    let lookup_table = read_lookup_table(&mut rtc.context.borrow_mut(), &table).await;
+    assert_eq!(
+        lookup_table.addresses.len(),
+        main_market.lookup_table_addresses().len()
+    );
+
+    // Refreshes every reserve of the market before the obligation
+    let jitosol = main_market.reserve(Asset::JitoSol).address;
+    let mut instructions: Vec<Instruction> = main_market
+        .reserves()
+        .iter()
+        .filter(|r| **r != jitosol)
+        .map(|r| compose_klend_refresh_reserve_ix(rtc.registry.get(r)))
+        .collect();
+    instructions.extend(main_market.compose_refresh_obligation_ixs(&obligation, &[jitosol], None));
+
+    let legacy =
+        process_instructions(&mut rtc.context.borrow_mut(), &alice.user, &instructions).await;
+    let v0 = process_v0_instructions(
+        &mut rtc.context.borrow_mut(),
+        &alice.user,
+        &instructions,
+        &[lookup_table],
+    )
+    .await;
+    println!(
+        "legacy: {} bytes, v0: {} bytes",
+        legacy.transaction_size, v0.transaction_size
+    );
+    assert!(v0.transaction_size < legacy.transaction_size);
+
+    // Reserves are loaded from the table, still writable
+    let mut writable = v0.writable_accounts.clone();
+    writable.sort();
+    let mut expected = legacy.writable_accounts.clone();
+    expected.sort();
+    assert_eq!(writable, expected);
+}
+
+#[test]
+fn test_klend_instruction_codegen() {
+    use integrate_klend_2::klend_instructions::{accounts, instruction};
+
+    assert_eq!(
+        instruction::BorrowObligationLiquidity::DISCRIMINATOR,
+        hash(b"global:borrow_obligation_liquidity").as_ref()[..8]
+    );
+
+    let owner = Pubkey::new_unique();
+    let ix = compose_klend_borrow_obligation_liquidity_ix(
+        &owner,
+        &EXAMPLE_OBLIGATION,
+        &MAIN_MARKET,
+        &lending_market_authority(&MAIN_MARKET),
+        &RESERVE_SOL_STATE,
+        &spl_token::native_mint::id(),
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        42,
+    );
+    assert_eq!(ix.program_id, KLEND_PROGRAM_ID);
+    assert_eq!(
+        ix.accounts.len(),
+        accounts::BorrowObligationLiquidity::NAMES.len()
+    );
+    assert_eq!(ix.accounts[0], AccountMeta::new(owner, true));
+    let args = instruction::BorrowObligationLiquidity::try_from_slice(&ix.data[8..]).unwrap();
+    assert_eq!(args.liquidity_amount, 42);
+
+    // Optional accounts left out are the program id, read only
+    let referrer_token_state = accounts::BorrowObligationLiquidity::NAMES
+        .iter()
+        .position(|name| *name == "referrer_token_state")
+        .unwrap();
+    assert_eq!(
+        ix.accounts[referrer_token_state],
+        AccountMeta::new_readonly(KLEND_PROGRAM_ID, false)
+    );
+}
+
+#[test]
+fn test_decode_transaction() {
+    let owner = Pubkey::new_unique();
+    let sol = ReserveInfo::from_fixture(&RESERVE_SOL_STATE, &DETACHED_FARMS);
+    let refresh_reserve = compose_klend_refresh_reserve_ix(&sol);
+    let borrow = compose_klend_borrow_obligation_liquidity_ix(
+        &owner,
+        &EXAMPLE_OBLIGATION,
+        &MAIN_MARKET,
+        &lending_market_authority(&MAIN_MARKET),
+        &RESERVE_SOL_STATE,
+        &spl_token::native_mint::id(),
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        42,
+    );
+
+    let decoded = decode_instruction(&borrow);
+    println!("{}", decoded);
+    assert_eq!(decoded.program, Some("klend"));
+    assert_eq!(decoded.name, Some("borrow_obligation_liquidity"));
+    assert_eq!(
+        decoded.args.as_deref(),
+        Some("BorrowObligationLiquidity { liquidity_amount: 42 }")
+    );
+    assert_eq!(decoded.account("obligation"), Some(&EXAMPLE_OBLIGATION));
+    assert_eq!(decoded.account("borrow_reserve"), Some(&RESERVE_SOL_STATE));
+    assert_eq!(
+        decoded.account("lending_market_authority"),
+        Some(&lending_market_authority(&MAIN_MARKET))
+    );
+
+    let refresh_obligation = compose_klend_refresh_obligation_ix(
+        &EXAMPLE_OBLIGATION,
+        &MAIN_MARKET,
+        &[RESERVE_JITOSOL_STATE, RESERVE_SOL_STATE],
+    );
+    let decoded = decode_instruction(&refresh_obligation);
+    assert_eq!(decoded.accounts[3].role, "remaining[1]");
+    assert_eq!(decoded.accounts[3].meta.pubkey, RESERVE_SOL_STATE);
+
+    // Not a klend or kfarm instruction
+    let transfer = system_instruction::transfer(&owner, &Pubkey::new_unique(), 1);
+    assert_eq!(decode_instruction(&transfer).name, None);
+
+    // Reserve and market passed through a lookup table decode to what the builders made
+    let lookup_table = AddressLookupTableAccount {
+        key: Pubkey::new_unique(),
+        addresses: vec![
+            MAIN_MARKET,
+            RESERVE_SOL_STATE,
+            lending_market_authority(&MAIN_MARKET),
+        ],
+    };
+    let instructions = vec![refresh_reserve, borrow];
+    let message = v0::Message::try_compile(
+        &owner,
+        &instructions,
+        std::slice::from_ref(&lookup_table),
+        Hash::default(),
+    )
+    .unwrap();
+    assert_eq!(message.address_table_lookups.len(), 1);
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default()],
+        message: VersionedMessage::V0(message),
+    };
+
+    let decoded = decode_transaction(&transaction, std::slice::from_ref(&lookup_table));
+    let expected: Vec<_> = instructions.iter().map(decode_instruction).collect();
+    assert_eq!(decoded, Ok(expected));
+
+    // Malformed messages are errors
+    assert_eq!(
+        decode_transaction(&transaction, &[]),
+        Err(DecodeError::MissingLookupTable(lookup_table.key))
+    );
+    let short_table = AddressLookupTableAccount {
+        key: lookup_table.key,
+        addresses: vec![MAIN_MARKET],
+    };
+    assert!(matches!(
+        decode_transaction(&transaction, &[short_table]),
+        Err(DecodeError::LookupIndexOutOfRange { .. })
+    ));
+    let mut malformed = transaction.clone();
+    let VersionedMessage::V0(message) = &mut malformed.message else {
+        unreachable!()
+    };
+    message.instructions[0].accounts[0] = u8::MAX;
+    assert!(matches!(
+        decode_transaction(&malformed, std::slice::from_ref(&lookup_table)),
+        Err(DecodeError::AccountIndexOutOfRange { index: u8::MAX, .. })
+    ));
+    let mut malformed = transaction.clone();
+    let VersionedMessage::V0(message) = &mut malformed.message else {
+        unreachable!()
+    };
+    message.header.num_readonly_signed_accounts = 2;
+    assert_eq!(
+        decode_transaction(&malformed, &[lookup_table]),
+        Err(DecodeError::InvalidHeader)
+    );
+}
+
+#[test]
+fn test_transaction_size() {
+    let payer = Pubkey::new_unique();
+    let recipients: Vec<Pubkey> = (0..70).map(|_| Pubkey::new_unique()).collect();
+    let transfers = |count: usize| -> Vec<Instruction> {
+        recipients[..count]
+            .iter()
+            .map(|recipient| system_instruction::transfer(&payer, recipient, 1))
+            .collect()
+    };
+
+    let size = check_transaction_size(&payer, &transfers(10), &[]);
+    assert!(size.fits());
+    // Payer, system program, compute budget program and the recipients
+    assert_eq!(size.account_count, 13);
+    assert_eq!(size.instructions.len(), 11);
+    assert_eq!(size.instructions[0].program_id, compute_budget::id());
+    assert_eq!(size.instructions[1].new_accounts.len(), 3);
+    assert_eq!(size.instructions[2].new_accounts, vec![recipients[1]]);
+
+    let size = check_transaction_size(&payer, &transfers(40), &[]);
+    assert!(size.size > MAX_TRANSACTION_SIZE);
+    assert!(!size.fits());
+    println!("{}", size.breakdown());
+    assert!(size.breakdown().contains(&recipients[39].to_string()));
+
+    // Recipients passed by index fit, up to the account lock limit
+    let lookup_table = AddressLookupTableAccount {
+        key: Pubkey::new_unique(),
+        addresses: recipients.clone(),
+    };
+    let size = check_transaction_size(&payer, &transfers(40), std::slice::from_ref(&lookup_table));
+    assert!(size.fits());
+    assert_eq!(size.lookup_accounts.len(), 40);
+
+    // One instruction reading every recipient, one byte per account
+    let metas = recipients
+        .iter()
+        .map(|recipient| AccountMeta::new_readonly(*recipient, false))
+        .collect();
+    let read_all = Instruction::new_with_bytes(system_program::id(), &[], metas);
+    let size = check_transaction_size(&payer, &[read_all], &[lookup_table]);
+    assert!(size.size <= MAX_TRANSACTION_SIZE);
+    assert!(size.account_count > MAX_TRANSACTION_ACCOUNTS);
+    assert!(!size.fits());
+    assert!(size.breakdown().contains("(lookup table)"));
 }
 
 #[tokio::test]
@@ -102,3 +1271,387 @@ async fn test_obligation() {
     admin.dump_obligation(&EXAMPLE_OBLIGATION).await;
     dump_reserve(&RESERVE_USDC_STATE);
 }
+
+#[test]
+fn test_reserve_registry() {
+    let registry = ReserveRegistry::from_fixtures(&KAMINO_RESERVES, &DETACHED_FARMS);
+
+    let jitosol = registry.get(&RESERVE_JITOSOL_STATE);
+    assert_eq!(jitosol.symbol, "JITOSOL");
+    assert_eq!(jitosol.decimals, 9);
+    assert_eq!(jitosol.lending_market, MAIN_MARKET);
+    assert_eq!(jitosol.liquidity_mint, JITOSOL_MINT);
+    assert_eq!(
+        jitosol.liquidity_supply_vault,
+        pubkey!("6sga1yRArgQRqa8Darhm54EBromEpV3z8iDAvMTVYXB3")
+    );
+    assert_eq!(
+        jitosol.collateral_mint,
+        pubkey!("9ucQp7thL38MDDTSER5ou24QnVSTZFLevDsZC1cAFkKy")
+    );
+    assert_eq!(
+        jitosol.collateral_supply_vault,
+        pubkey!("7y5Nko765HcZiTd2gFtxorELuJZcbQqmrmTbUVoiwGyS")
+    );
+    assert!(!jitosol.has_farm_collateral());
+    assert_eq!(jitosol.scope_prices, KAMINO_SCOPE_PRICES);
+
+    let sol = registry.get(&RESERVE_SOL_STATE);
+    assert_eq!(sol.liquidity_mint, spl_token::native_mint::id());
+    assert_eq!(
+        sol.liquidity_fee_vault,
+        pubkey!("3JNof8s453bwG5UqiXBLJc77NRQXezYYEBbk3fqnoKph")
+    );
+    assert_eq!(
+        sol.farm_collateral,
+        pubkey!("955xWFhSDcDiUgUr4sBRtCpTLiMd4H5uZLAmgtP3R3sX")
+    );
+
+    let usdc = registry
+        .find_by_liquidity_mint(
+            &MAIN_MARKET,
+            &pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+        )
+        .unwrap();
+    assert_eq!(usdc.address, RESERVE_USDC_STATE);
+    assert_eq!(usdc.decimals, 6);
+    let data = read_account_data(&format!("{}.bin", RESERVE_USDC_STATE));
+    let on_chain = ReserveInfo::from_account_data(&RESERVE_USDC_STATE, &data).unwrap();
+    assert!(DETACHED_FARMS.contains(&on_chain.farm_collateral));
+    // Truncated data
+    assert!(matches!(
+        ReserveInfo::from_account_data(&RESERVE_USDC_STATE, &data[..4]),
+        Err(ReserveDataError::NotAReserve)
+    ));
+    assert!(matches!(
+        ReserveInfo::from_account_data(&RESERVE_USDC_STATE, &data[..100]),
+        Err(ReserveDataError::InvalidLayout)
+    ));
+    assert!(!usdc.has_farm_collateral());
+    assert_eq!(
+        registry
+            .find_by_symbol(&MAIN_MARKET, "usdc")
+            .unwrap()
+            .address,
+        RESERVE_USDC_STATE
+    );
+}
+
+#[test]
+fn test_inspect_account_dumps() {
+    let farm = pubkey!("955xWFhSDcDiUgUr4sBRtCpTLiMd4H5uZLAmgtP3R3sX");
+    let decode =
+        |address: &Pubkey| KaminoAccount::decode(&read_account_data(&format!("{}.bin", address)));
+
+    let types: Vec<&str> = [MAIN_MARKET, RESERVE_USDC_STATE, EXAMPLE_OBLIGATION, farm]
+        .iter()
+        .map(|address| decode(address).unwrap().type_name())
+        .collect();
+    assert_eq!(
+        types,
+        ["LendingMarket", "Reserve", "Obligation", "FarmState"]
+    );
+    assert!(decode(&JITOSOL_MINT).is_none());
+
+    let Some(KaminoAccount::FarmState(farm_state)) = decode(&farm) else {
+        panic!("{} is not a farm", farm);
+    };
+    assert_eq!(farm_state.is_farm_delegated, 1);
+    assert_eq!(
+        farm_state.delegate_authority,
+        lending_market_authority(&MAIN_MARKET)
+    );
+    assert_eq!(farm_state.num_reward_tokens, 2);
+
+    // Same account as exported by `solana account --output json`
+    let data = read_account_data(&format!("{}.bin", farm));
+    let export = json!({
+        "pubkey": farm.to_string(),
+        "account": {
+            "lamports": 1_000_000,
+            "data": [BASE64.encode(&data), "base64"],
+            "owner": KFARM_PROGRAM_ID.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "space": data.len(),
+        },
+    });
+    let dump = parse_account_dump(export.to_string().as_bytes()).unwrap();
+    assert_eq!(dump.address, Some(farm));
+    assert_eq!(dump.owner, Some(KFARM_PROGRAM_ID));
+    assert_eq!(dump.data, data);
+
+    let raw = parse_account_dump(&data).unwrap();
+    assert_eq!(raw.address, None);
+    assert_eq!(raw.data, data);
+
+    let farm_json = KaminoAccount::decode(&dump.data)
+        .unwrap()
+        .to_json(dump.address.as_ref());
+    assert_eq!(farm_json["address"], farm.to_string());
+    assert_eq!(farm_json["type"], "FarmState");
+    assert_eq!(farm_json["fields"]["num_reward_tokens"], 2);
+
+    let reserve_json = decode(&RESERVE_USDC_STATE).unwrap().to_json(None);
+    assert_eq!(reserve_json["fields"]["config.token_info.name"], "USDC");
+    assert_eq!(
+        reserve_json["fields"]["utilization_rate"],
+        ReserveSnapshot::from_fixture(&RESERVE_USDC_STATE)
+            .utilization_rate
+            .to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_and_withdraw_in_one_instruction() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+
+    alice.klend_init_user_metadata().await;
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+    rtc.mint_asset(alice, Asset::JitoSol, 50_000_000_000).await;
+
+    main_market
+        .deposit_reserve_liquidity_and_obligation_collateral(
+            &alice.user,
+            &obligation,
+            Asset::JitoSol,
+            50_000_000_000,
+        )
+        .await;
+    alice.assert_mint_balance(JITOSOL_MINT, 0).await;
+
+    let reserve = main_market.get_reserve_account(Asset::JitoSol).await;
+    let state = main_market.get_obligation(&obligation).await;
+    assert_eq!(state.deposits[0].deposit_reserve, RESERVE_JITOSOL_STATE);
+    assert_eq!(
+        state.deposits[0].deposited_amount,
+        liquidity_to_collateral(&reserve, 50_000_000_000)
+    );
+
+    main_market
+        .withdraw_obligation_collateral_and_redeem_reserve_collateral(
+            &alice.user,
+            &obligation,
+            Asset::JitoSol,
+            u64::MAX,
+        )
+        .await;
+
+    // klend closes the obligation it leaves empty
+    assert!(!rtc.obligations().await.contains(&obligation));
+    // Rounding down twice, through collateral and back
+    let redeemed = alice.mint_balance(&JITOSOL_MINT).await;
+    assert!((50_000_000_000 - 2..=50_000_000_000).contains(&redeemed));
+}
+
+#[tokio::test]
+async fn test_elevation_group() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+
+    // JitoSOL collateral against SOL debt
+    let group = main_market
+        .get_lending_market()
+        .await
+        .elevation_groups
+        .iter()
+        .find(|group| group.id != 0 && group.debt_reserve == RESERVE_SOL_STATE)
+        .cloned()
+        .unwrap();
+    let jitosol = main_market.get_reserve_account(Asset::JitoSol).await;
+    assert!(jitosol.config.elevation_groups.contains(&group.id));
+    assert!(group.ltv_pct > jitosol.config.loan_to_value_pct);
+
+    alice.klend_init_user_metadata().await;
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+    rtc.mint_asset(alice, Asset::JitoSol, 50_000_000_000).await;
+    main_market
+        .deposit_reserve_liquidity_and_obligation_collateral(
+            &alice.user,
+            &obligation,
+            Asset::JitoSol,
+            50_000_000_000,
+        )
+        .await;
+
+    // Above the JitoSOL LTV, within the one of the group
+    let borrow = 35_000_000_000;
+    assert_klend_error(
+        main_market
+            .try_borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, borrow)
+            .await,
+        LendingError::BorrowTooLarge,
+    );
+
+    main_market
+        .request_elevation_group(&alice.user, &obligation, group.id)
+        .await;
+    assert_eq!(
+        main_market
+            .get_obligation(&obligation)
+            .await
+            .elevation_group,
+        group.id
+    );
+
+    main_market
+        .borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, borrow)
+        .await;
+
+    // Health at the group LTV and liquidation threshold, as refresh_obligation computes it
+    main_market
+        .refresh_obligation(&alice.user, &obligation)
+        .await;
+    let state = main_market.get_obligation(&obligation).await;
+    let health = main_market.obligation_health(&obligation).await;
+    let tolerance = Fraction::from_num(0.0001);
+    let deposited = Fraction::from_bits(state.deposited_value_sf);
+    let ltv = Fraction::from_bits(state.borrow_factor_adjusted_debt_value_sf) / deposited;
+    assert!(health.ltv.abs_diff(ltv) < tolerance);
+    let max_ltv = Fraction::from_bits(state.allowed_borrow_value_sf) / deposited;
+    assert!(health.max_ltv.abs_diff(max_ltv) < tolerance);
+    assert!(
+        health
+            .max_ltv
+            .abs_diff(Fraction::from_num(group.ltv_pct) / 100)
+            < tolerance
+    );
+    assert!(
+        health
+            .liquidation_ltv
+            .abs_diff(Fraction::from_num(group.liquidation_threshold_pct) / 100)
+            < tolerance
+    );
+
+    main_market
+        .repay_obligation_liquidity(&alice.user, &obligation, Asset::Sol, borrow + 1_000)
+        .await;
+    assert_eq!(
+        obligation_reserves(&main_market.get_obligation(&obligation).await),
+        vec![main_market.reserve(Asset::JitoSol).address]
+    );
+}
+
+#[tokio::test]
+async fn test_referrer() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+    let referrer = alice.user.pubkey();
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+
+    alice.klend_init_user_metadata().await;
+    alice.klend_init_referrer_state_and_short_url("alice").await;
+    for address in [referrer_state(&referrer), referrer_short_url("alice")] {
+        assert_eq!(alice.get_account(address).await.owner, KLEND_PROGRAM_ID);
+    }
+
+    main_market
+        .init_referrer_token_state(&alice.user, &referrer, Asset::Sol)
+        .await;
+    let token_state = referrer_token_state(&referrer, &RESERVE_SOL_STATE);
+    let mut account = alice.get_account(token_state).await;
+    assert_eq!(account.owner, KLEND_PROGRAM_ID);
+    assert_eq!(account.data[8..40], referrer.to_bytes());
+    assert_eq!(
+        account.data[40..72],
+        spl_token::native_mint::id().to_bytes()
+    );
+
+    // Fees reach referrers as obligations they referred accrue interest, credit some directly:
+    // amount_unclaimed_sf of the token state and accumulated_referrer_fees_sf of the reserve
+    let fees: u64 = 1_000_000;
+    account.data[72..88].copy_from_slice(&Fraction::from(fees).to_bits().to_le_bytes());
+    rtc.context
+        .borrow_mut()
+        .set_account(&token_state, &AccountSharedData::from(account));
+    let mut reserve = main_market.get_reserve_account(Asset::Sol).await;
+    reserve.liquidity.accumulated_referrer_fees_sf += Fraction::from(fees).to_bits();
+    main_market.set_reserve(Asset::Sol, &reserve).await;
+
+    main_market
+        .withdraw_referrer_fees(&alice.user, Asset::Sol)
+        .await;
+    assert_eq!(
+        alice.mint_balance(&spl_token::native_mint::id()).await,
+        fees
+    );
+    let reserve = main_market.get_reserve_account(Asset::Sol).await;
+    assert_eq!(reserve.liquidity.accumulated_referrer_fees_sf, 0);
+}
+
+#[tokio::test]
+async fn test_socialize_loss() {
+    let rtc = RateXTestContext::new().await;
+    let alice = &rtc.users[1];
+    let main_market = rtc.lending_market_client(&MAIN_MARKET);
+
+    rtc.set_sysvar_clock(1730163565).await; // KAMINO_SCOPE_PRICES
+
+    let mut market = main_market.get_lending_market().await;
+    market.risk_council = rtc.admin.pubkey();
+    main_market.set_lending_market(&market).await;
+
+    alice.klend_init_user_metadata().await;
+    let obligation = main_market.init_obligation(&alice.user, 0, 0).await;
+    rtc.mint_asset(alice, Asset::JitoSol, 50_000_000_000).await;
+    main_market
+        .deposit_reserve_liquidity_and_obligation_collateral(
+            &alice.user,
+            &obligation,
+            Asset::JitoSol,
+            50_000_000_000,
+        )
+        .await;
+    main_market
+        .borrow_obligation_liquidity(&alice.user, &obligation, Asset::Sol, 10_000_000_000)
+        .await;
+
+    assert_klend_error(
+        main_market
+            .try_socialize_loss(&rtc.admin, &obligation, Asset::Sol, u64::MAX)
+            .await,
+        LendingError::CannotSocializeObligationWithCollateral,
+    );
+
+    // Leave the debt without collateral, as a liquidation taking it all would
+    let mut state = main_market.get_obligation(&obligation).await;
+    let deposit = &mut state.deposits[0];
+    deposit.deposit_reserve = Pubkey::default();
+    deposit.deposited_amount = 0;
+    deposit.market_value_sf = 0;
+    state.deposited_value_sf = 0;
+    main_market.set_obligation(&obligation, &state).await;
+
+    let watched = [obligation, RESERVE_SOL_STATE];
+    let before = rtc.snapshot(&watched).await;
+    main_market
+        .socialize_loss(&rtc.admin, &obligation, Asset::Sol, u64::MAX)
+        .await;
+    let diff = before.diff(&rtc.snapshot(&watched).await);
+    println!("{}", diff.to_table());
+
+    assert!(obligation_reserves(&main_market.get_obligation(&obligation).await).is_empty());
+    let socialized = diff
+        .field(
+            &obligation,
+            &format!("borrows[{}].borrowed_amount_sf", RESERVE_SOL_STATE),
+        )
+        .unwrap()
+        .delta();
+    assert_eq!(socialized, -10_000_000_000.0);
+    // The whole reserve also accrues interest over the slots since the borrow
+    let reserve_delta = diff
+        .field(&RESERVE_SOL_STATE, "liquidity.borrowed_amount_sf")
+        .unwrap()
+        .delta();
+    assert!(reserve_delta < -9_990_000_000.0 && reserve_delta > socialized);
+}
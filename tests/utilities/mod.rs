@@ -1,2 +0,0 @@
-pub mod helper;
-pub mod kamino;